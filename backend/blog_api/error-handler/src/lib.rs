@@ -1,12 +1,21 @@
-use warp::{Rejection, Reply};
-use warp::reject::Reject;
+use warp::{Filter, Rejection, Reply};
+use warp::reject::{
+  InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader, PayloadTooLarge, Reject,
+  UnsupportedMediaType,
+};
 use warp::cors::CorsForbidden;
 use warp::body::BodyDeserializeError;
+use warp::http::header::{HeaderValue, CONTENT_TYPE, WWW_AUTHENTICATE};
 use warp::hyper::StatusCode;
 use reqwest::Error as ReqwestError;
+use serde::Serialize;
 
 use tracing::{event, Level};
+use std::convert::Infallible;
 use std::fmt;
+use std::sync::LazyLock;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub struct ApiLayerError {
@@ -24,61 +33,456 @@ impl std::fmt::Display for ApiLayerError {
 pub enum ApiError {
   ParseError(std::num::ParseIntError),
   MissingParamError,
-  DatabaseQueryError,
+  InvalidParamError(String),
+  IdMismatchError { path: i32, body: i32 },
+  DatabaseQueryError(sqlx::Error),
+  QuestionNotFoundError(i32),
+  QuestionHasAnswersError { id: i32, answers: Vec<i32> },
+  AnswerNotFoundError(i32),
+  RevisionNotFoundError { question: i32, revision: i32 },
   ExternalApiError(ReqwestError),
   ClientError(ApiLayerError),
   ServerError(ApiLayerError),
+  ModerationUnavailableError,
+  ArgonLibraryError(argon2::Error),
+  /// work moved off the async workers panicked or was cancelled
+  BlockingTaskError(tokio::task::JoinError),
+  WrongPasswordError,
+  UnauthorizedError,
+  ForbiddenError,
+  DuplicateAccountError,
 }
 
 impl std::fmt::Display for ApiError {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match &*self {
-      ApiError::ParseError(ref err) => writeln!(f, "could not parse parameter: {}", err),
-      ApiError::MissingParamError => writeln!(f, "missing parameter"), 
-      ApiError::DatabaseQueryError => write!(f, "cannot update, invalid data."),
+      ApiError::ParseError(ref err) => write!(f, "could not parse parameter: {}", err),
+      ApiError::MissingParamError => write!(f, "missing parameter"),
+      ApiError::InvalidParamError(reason) => write!(f, "invalid parameter: {}", reason),
+      ApiError::IdMismatchError { path, body } => write!(f, "body id {} does not match path id {}", body, path),
+      ApiError::DatabaseQueryError(err) => write!(f, "database query failed: {}", err),
+      ApiError::QuestionNotFoundError(id) => write!(f, "question {} not found", id),
+      ApiError::QuestionHasAnswersError { id, answers } => {
+        write!(f, "question {} still has {} answer(s), delete them first", id, answers.len())
+      }
+      ApiError::AnswerNotFoundError(id) => write!(f, "answer {} not found", id),
+      ApiError::RevisionNotFoundError { question, revision } => {
+        write!(f, "question {} has no revision {}", question, revision)
+      }
       ApiError::ExternalApiError(err) => write!(f, "cannot execute: {}", err),
       ApiError::ClientError(err) => write!(f, "external client error: {}", err),
       ApiError::ServerError(err) => write!(f, "external server error: {}", err),
+      ApiError::ModerationUnavailableError => write!(f, "content moderation is unavailable, try again later"),
+      ApiError::ArgonLibraryError(_) | ApiError::BlockingTaskError(_) => write!(f, "cannot verify password"),
+      ApiError::WrongPasswordError => write!(f, "wrong e-mail or password"),
+      ApiError::UnauthorizedError => write!(f, "missing or invalid bearer token"),
+      ApiError::ForbiddenError => write!(f, "only the author or an admin can change this"),
+      ApiError::DuplicateAccountError => write!(f, "an account with this e-mail already exists"),
     }
   }
 }
 
+impl std::error::Error for ApiError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ApiError::ParseError(err) => Some(err),
+      ApiError::DatabaseQueryError(err) => Some(err),
+      ApiError::ExternalApiError(err) => Some(err),
+      ApiError::ArgonLibraryError(err) => Some(err),
+      ApiError::BlockingTaskError(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+/// foreign keys broken by deleting the referenced row, as named in the `foreign_key_names` migration.
+/// Postgres reports the same constraint for both sides, so this goes by what the api can do:
+/// answers check for their question before they are inserted, but a purged question can still
+/// have answers. The referenced rows of every other key are never deleted or take their rows along.
+const RESTRICTING_FOREIGN_KEYS: &[&str] = &["answers_question_fkey"];
+
+/// what a failed query means for the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DatabaseFailure {
+  /// `RowNotFound`
+  NotFound,
+  /// unique violation
+  Conflict,
+  /// foreign key violation, the referenced row is missing
+  MissingReference,
+  /// foreign key violation of a key in `RESTRICTING_FOREIGN_KEYS`, other rows still point at this one
+  StillReferenced,
+  /// not null, check or data exceptions
+  InvalidData,
+  /// pool or statement timeout
+  Timeout,
+  /// connection lost, refused or the server is shutting down
+  Unavailable,
+  Other,
+}
+
+impl DatabaseFailure {
+  fn of(err: &sqlx::Error) -> Self {
+    match err {
+      sqlx::Error::RowNotFound => DatabaseFailure::NotFound,
+      sqlx::Error::PoolTimedOut => DatabaseFailure::Timeout,
+      sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed => {
+        DatabaseFailure::Unavailable
+      }
+      sqlx::Error::Database(db) => {
+        let code = db.code().unwrap_or_default();
+        match code.as_ref() {
+          "23505" => DatabaseFailure::Conflict,
+          "23503" => match db.constraint() {
+            Some(constraint) if RESTRICTING_FOREIGN_KEYS.contains(&constraint) => DatabaseFailure::StillReferenced,
+            _ => DatabaseFailure::MissingReference,
+          },
+          "23502" | "23514" => DatabaseFailure::InvalidData,
+          "57014" => DatabaseFailure::Timeout,
+          "57P01" | "57P02" | "57P03" => DatabaseFailure::Unavailable,
+          code if code.starts_with("22") => DatabaseFailure::InvalidData,
+          code if code.starts_with("08") || code.starts_with("53") => DatabaseFailure::Unavailable,
+          _ => DatabaseFailure::Other,
+        }
+      }
+      _ => DatabaseFailure::Other,
+    }
+  }
+
+  fn code(self) -> &'static str {
+    match self {
+      DatabaseFailure::NotFound => "not_found",
+      DatabaseFailure::Conflict => "conflict",
+      DatabaseFailure::MissingReference => "reference_not_found",
+      DatabaseFailure::StillReferenced => "still_referenced",
+      DatabaseFailure::InvalidData => "invalid_data",
+      DatabaseFailure::Timeout => "database_timeout",
+      DatabaseFailure::Unavailable => "database_unavailable",
+      DatabaseFailure::Other => "database_query",
+    }
+  }
+
+  fn status(self) -> StatusCode {
+    match self {
+      DatabaseFailure::NotFound | DatabaseFailure::MissingReference => StatusCode::NOT_FOUND,
+      DatabaseFailure::Conflict | DatabaseFailure::StillReferenced => StatusCode::CONFLICT,
+      DatabaseFailure::InvalidData => StatusCode::BAD_REQUEST,
+      DatabaseFailure::Timeout | DatabaseFailure::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+      DatabaseFailure::Other => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+
+  /// sql and constraint names stay in the logs
+  fn detail(self) -> &'static str {
+    match self {
+      DatabaseFailure::NotFound => "the requested item does not exist",
+      DatabaseFailure::Conflict => "an item with the same value already exists",
+      DatabaseFailure::MissingReference => "the item refers to something that does not exist",
+      DatabaseFailure::StillReferenced => "other items still refer to this one",
+      DatabaseFailure::InvalidData => "the data is invalid",
+      DatabaseFailure::Timeout => "the database did not answer in time, try again later",
+      DatabaseFailure::Unavailable => "the database is unavailable, try again later",
+      DatabaseFailure::Other => "the query failed",
+    }
+  }
+}
+
+impl ApiError {
+  /// stable snake_case name of the error, used as a metric label.
+  /// database errors are named after what went wrong, not the variant
+  pub fn kind(&self) -> &'static str {
+    match self {
+      ApiError::ParseError(_) => "parse",
+      ApiError::MissingParamError => "missing_param",
+      ApiError::InvalidParamError(_) => "invalid_param",
+      ApiError::IdMismatchError { .. } => "id_mismatch",
+      ApiError::DatabaseQueryError(err) => DatabaseFailure::of(err).code(),
+      ApiError::QuestionNotFoundError(_) => "question_not_found",
+      ApiError::QuestionHasAnswersError { .. } => "question_has_answers",
+      ApiError::AnswerNotFoundError(_) => "answer_not_found",
+      ApiError::RevisionNotFoundError { .. } => "revision_not_found",
+      ApiError::ExternalApiError(_) => "external_api",
+      ApiError::ClientError(_) => "upstream_client",
+      ApiError::ServerError(_) => "upstream_server",
+      ApiError::ModerationUnavailableError => "moderation_unavailable",
+      ApiError::ArgonLibraryError(_) => "argon",
+      ApiError::BlockingTaskError(_) => "blocking_task",
+      ApiError::WrongPasswordError => "wrong_password",
+      ApiError::UnauthorizedError => "unauthorized",
+      ApiError::ForbiddenError => "forbidden",
+      ApiError::DuplicateAccountError => "duplicate_account",
+    }
+  }
+}
+
+/// `ApiError`s that reached `handle_errors`, by kind, in the default prometheus registry
+static API_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+  register_int_counter_vec!("api_errors_total", "API errors turned into replies, by kind", &["kind"])
+    .expect("api_errors_total registered twice")
+});
+
+/// RFC 7807 problem details, sent as `application/problem+json`
+#[derive(Debug, Serialize)]
+struct Problem {
+  #[serde(rename = "type")]
+  problem_type: &'static str,
+  title: &'static str,
+  status: u16,
+  detail: String,
+  /// stable and machine readable, unlike `detail`
+  code: &'static str,
+  /// also sent as `x-request-id`, and logged next to the error
+  request_id: String,
+  /// ids of the answers blocking a delete
+  #[serde(skip_serializing_if = "Option::is_none")]
+  answers: Option<Vec<i32>>,
+}
+
 impl Reject for ApiError {}
 impl Reject for ApiLayerError {}
 
-pub async fn handle_errors(r: Rejection) -> Result<impl Reply, Rejection> {
-  if let Some(crate::ApiError::DatabaseQueryError) = r.find() {
-    event!(Level::ERROR, "database query error");
-    Ok(warp::reply::with_status(
-      crate::ApiError::DatabaseQueryError.to_string(), 
-      StatusCode::UNPROCESSABLE_ENTITY
-    ))
-  } else if let Some(error) = r.find::<CorsForbidden>() {
+impl ApiError {
+  pub fn status(&self) -> StatusCode {
+    match self {
+      ApiError::ParseError(_)
+      | ApiError::MissingParamError
+      | ApiError::InvalidParamError(_)
+      | ApiError::IdMismatchError { .. } => StatusCode::BAD_REQUEST,
+      ApiError::DatabaseQueryError(err) => DatabaseFailure::of(err).status(),
+      ApiError::QuestionNotFoundError(_)
+      | ApiError::AnswerNotFoundError(_)
+      | ApiError::RevisionNotFoundError { .. } => StatusCode::NOT_FOUND,
+      ApiError::QuestionHasAnswersError { .. } => StatusCode::CONFLICT,
+      ApiError::ExternalApiError(_) | ApiError::ClientError(_) | ApiError::ServerError(_) => StatusCode::BAD_GATEWAY,
+      ApiError::ModerationUnavailableError => StatusCode::SERVICE_UNAVAILABLE,
+      ApiError::ArgonLibraryError(_) | ApiError::BlockingTaskError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      ApiError::WrongPasswordError | ApiError::UnauthorizedError => StatusCode::UNAUTHORIZED,
+      ApiError::ForbiddenError => StatusCode::FORBIDDEN,
+      ApiError::DuplicateAccountError => StatusCode::CONFLICT,
+    }
+  }
+
+  /// what the client gets to read, upstream details stay in the logs
+  fn detail(&self) -> String {
+    match self {
+      ApiError::ExternalApiError(_) | ApiError::ClientError(_) | ApiError::ServerError(_) => {
+        "the content moderation service failed".to_string()
+      }
+      ApiError::DatabaseQueryError(err) => DatabaseFailure::of(err).detail().to_string(),
+      error => error.to_string(),
+    }
+  }
+}
+
+fn problem(status: StatusCode, code: &'static str, detail: String, request_id: &str) -> warp::reply::Response {
+  problem_with(status, code, detail, request_id, None)
+}
+
+fn problem_with(
+  status: StatusCode,
+  code: &'static str,
+  detail: String,
+  request_id: &str,
+  answers: Option<Vec<i32>>,
+) -> warp::reply::Response {
+  let body = Problem {
+    problem_type: "about:blank",
+    title: status.canonical_reason().unwrap_or("Error"),
+    status: status.as_u16(),
+    detail,
+    code,
+    request_id: request_id.to_string(),
+    answers,
+  };
+
+  let mut res = warp::reply::with_status(warp::reply::json(&body), status).into_response();
+  res.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+  res
+}
+
+/// id of one request, on its tracing span, in the `X-Request-Id` header and in problem bodies
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// generates the id once the request comes in and records it on the `request_id` field of the current span
+pub fn request_id() -> impl Filter<Extract = (RequestId,), Error = Infallible> + Clone {
+  warp::any().map(|| {
+    let request_id = Uuid::new_v4().to_string();
+    tracing::Span::current().record("request_id", &tracing::field::display(&request_id));
+    RequestId(request_id)
+  })
+}
+
+/// the reply, or the problem for the rejection, with `X-Request-Id` set either way
+pub fn respond(request_id: RequestId, res: Result<warp::reply::Response, Rejection>) -> warp::reply::Response {
+  let mut res = match res {
+    Ok(res) => res,
+    Err(r) => handle_errors(&request_id.0, r),
+  };
+  if let Ok(request_id) = HeaderValue::from_str(&request_id.0) {
+    res.headers_mut().insert("x-request-id", request_id);
+  }
+  res
+}
+
+pub fn handle_errors(request_id: &str, r: Rejection) -> warp::reply::Response {
+
+  if let Some(error) = r.find::<ApiError>() {
+    API_ERRORS.with_label_values(&[error.kind()]).inc();
+
+    let status = error.status();
+    let answers = match error {
+      ApiError::QuestionHasAnswersError { answers, .. } => Some(answers.clone()),
+      _ => None,
+    };
+    let mut res = problem_with(status, error.kind(), error.detail(), request_id, answers);
+    let source = std::error::Error::source(error).map(|source| format!("{:?}", source));
+
+    if status.is_server_error() {
+      event!(Level::ERROR, request_id = %request_id, code = error.kind(), source = ?source, "{}", error);
+    } else {
+      event!(Level::INFO, request_id = %request_id, code = error.kind(), source = ?source, "{}", error);
+    }
+
+    if let ApiError::UnauthorizedError = error {
+      res.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    return res;
+  }
+
+  // warp's own rejections, a mismatched method only counts once the path matched
+  let (status, code, detail) = if let Some(error) = r.find::<CorsForbidden>() {
     event!(Level::ERROR, "CORS forbidden error: {}", error);
-    Ok(warp::reply::with_status(
-      error.to_string(),
-      StatusCode::FORBIDDEN
-    ))
-  } else if let Some(error) = r.find::<ApiError>() {
-    Ok(warp::reply::with_status(
-      error.to_string(),
-      StatusCode::RANGE_NOT_SATISFIABLE
-    ))
+    (StatusCode::FORBIDDEN, "cors_forbidden", error.to_string())
   } else if let Some(error) = r.find::<BodyDeserializeError>() {
-    Ok(warp::reply::with_status(
-      error.to_string(),
-      StatusCode::UNPROCESSABLE_ENTITY
-    ))
-  } else if let Some(crate::ApiError::ExternalApiError(e)) = r.find() {
-    event!(Level::ERROR, "external api error: {}", e);
-    Ok(warp::reply::with_status(
-      "Internal server error".to_string(),
-      StatusCode::INTERNAL_SERVER_ERROR
-    ))
+    (StatusCode::BAD_REQUEST, "invalid_body", error.to_string())
+  } else if let Some(error) = r.find::<InvalidQuery>() {
+    (StatusCode::BAD_REQUEST, "invalid_query", error.to_string())
+  } else if let Some(error) = r.find::<MissingHeader>() {
+    (StatusCode::BAD_REQUEST, "missing_header", error.to_string())
+  } else if let Some(error) = r.find::<InvalidHeader>() {
+    (StatusCode::BAD_REQUEST, "invalid_header", error.to_string())
+  } else if let Some(error) = r.find::<LengthRequired>() {
+    (StatusCode::LENGTH_REQUIRED, "length_required", error.to_string())
+  } else if let Some(error) = r.find::<PayloadTooLarge>() {
+    (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", error.to_string())
+  } else if let Some(error) = r.find::<UnsupportedMediaType>() {
+    (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", error.to_string())
+  } else if let Some(error) = r.find::<MethodNotAllowed>() {
+    (StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", error.to_string())
+  } else if r.is_not_found() {
+    (StatusCode::NOT_FOUND, "route_not_found", "Route not found".to_string())
   } else {
-    Ok(warp::reply::with_status(
-      "Route not found".to_string(),
-      StatusCode::NOT_FOUND
-    ))
+    event!(Level::ERROR, request_id = %request_id, "unhandled rejection: {:?}", r);
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal", "Internal server error".to_string())
+  };
+
+  problem(status, code, detail, request_id)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::borrow::Cow;
+
+  /// what postgres reports for a constraint violation
+  #[derive(Debug)]
+  struct Violation {
+    code: &'static str,
+    constraint: &'static str,
+  }
+
+  impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "{} violates {}", self.code, self.constraint)
+    }
+  }
+
+  impl std::error::Error for Violation {}
+
+  impl sqlx::error::DatabaseError for Violation {
+    fn message(&self) -> &str {
+      "violation"
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+      Some(Cow::Borrowed(self.code))
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+      self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+      self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+      self
+    }
+
+    fn constraint(&self) -> Option<&str> {
+      Some(self.constraint)
+    }
+  }
+
+  fn violation(code: &'static str, constraint: &'static str) -> ApiError {
+    ApiError::DatabaseQueryError(sqlx::Error::Database(Box::new(Violation { code, constraint })))
+  }
+
+  #[test]
+  fn restricting_foreign_keys_are_still_referenced() {
+    let error = violation("23503", "answers_question_fkey");
+    assert_eq!((error.status(), error.kind()), (StatusCode::CONFLICT, "still_referenced"));
+  }
+
+  #[test]
+  fn other_foreign_keys_miss_their_reference() {
+    let constraints = [
+      "questions_account_fkey",
+      "answers_account_fkey",
+      "moderation_reviews_question_fkey",
+      "moderation_reviews_answer_fkey",
+      "question_revisions_question_fkey",
+      "question_revisions_account_fkey",
+      "question_votes_question_fkey",
+      "question_votes_account_fkey",
+      "answer_votes_answer_fkey",
+      "answer_votes_account_fkey",
+      // the name postgres picked before the `foreign_key_names` migration
+      "answers_corresponding_question_fkey",
+    ];
+
+    for constraint in constraints {
+      let error = violation("23503", constraint);
+      assert_eq!((error.status(), error.kind()), (StatusCode::NOT_FOUND, "reference_not_found"), "{}", constraint);
+    }
+  }
+
+  #[test]
+  fn other_violations_go_by_their_code() {
+    let cases = [
+      ("23505", "accounts_email_key", StatusCode::CONFLICT, "conflict"),
+      ("23502", "questions_title_not_null", StatusCode::BAD_REQUEST, "invalid_data"),
+      ("23514", "answer_votes_direction_check", StatusCode::BAD_REQUEST, "invalid_data"),
+      ("22001", "", StatusCode::BAD_REQUEST, "invalid_data"),
+    ];
+
+    for (code, constraint, status, kind) in cases {
+      let error = violation(code, constraint);
+      assert_eq!((error.status(), error.kind()), (status, kind), "{}", code);
+    }
+  }
+
+  #[tokio::test]
+  async fn violations_reach_the_client_as_problems() {
+    let res = handle_errors("request-1", warp::reject::custom(violation("23503", "answers_question_fkey")));
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/problem+json");
+
+    let body = warp::hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("\"code\":\"still_referenced\""), "{}", body);
+    assert!(!body.contains("answers_question_fkey"), "constraint names stay in the logs");
   }
 }
@@ -0,0 +1,3 @@
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+UPDATE questions SET updated_at = created_at;
@@ -0,0 +1,4 @@
+ALTER TABLE answers DROP COLUMN IF EXISTS account_id;
+ALTER TABLE questions DROP COLUMN IF EXISTS account_id;
+
+DROP TABLE IF EXISTS accounts;
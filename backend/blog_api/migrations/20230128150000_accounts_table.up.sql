@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS accounts (
+  id serial PRIMARY KEY,
+  email VARCHAR (255) NOT NULL UNIQUE,
+  password VARCHAR (255) NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS account_id integer REFERENCES accounts;
+ALTER TABLE answers ADD COLUMN IF NOT EXISTS account_id integer REFERENCES accounts;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS moderation_reviews (
+  id serial PRIMARY KEY,
+  question_id integer REFERENCES questions ON DELETE CASCADE,
+  answer_id integer REFERENCES answers ON DELETE CASCADE,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS answers_deleted_at;
+DROP INDEX IF EXISTS questions_deleted_at;
+
+ALTER TABLE answers DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE questions DROP COLUMN IF EXISTS deleted_at;
@@ -0,0 +1,5 @@
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE answers ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS questions_deleted_at ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS answers_deleted_at ON answers (deleted_at) WHERE deleted_at IS NOT NULL;
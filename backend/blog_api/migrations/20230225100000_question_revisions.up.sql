@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS question_revisions (
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  revision integer NOT NULL,
+  title VARCHAR (255) NOT NULL,
+  content TEXT NOT NULL,
+  tags TEXT [],
+  account_id integer REFERENCES accounts,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (question_id, revision)
+);
+
+-- what every existing question looks like now becomes its first revision
+INSERT INTO question_revisions (question_id, revision, title, content, tags, account_id, created_at)
+  SELECT id, 1, title, content, tags, account_id, updated_at FROM questions
+  ON CONFLICT DO NOTHING;
@@ -0,0 +1,12 @@
+-- fixed names for the foreign keys, the error handler classifies violations by them
+ALTER TABLE questions RENAME CONSTRAINT questions_account_id_fkey TO questions_account_fkey;
+ALTER TABLE answers RENAME CONSTRAINT answers_corresponding_question_fkey TO answers_question_fkey;
+ALTER TABLE answers RENAME CONSTRAINT answers_account_id_fkey TO answers_account_fkey;
+ALTER TABLE moderation_reviews RENAME CONSTRAINT moderation_reviews_question_id_fkey TO moderation_reviews_question_fkey;
+ALTER TABLE moderation_reviews RENAME CONSTRAINT moderation_reviews_answer_id_fkey TO moderation_reviews_answer_fkey;
+ALTER TABLE question_revisions RENAME CONSTRAINT question_revisions_question_id_fkey TO question_revisions_question_fkey;
+ALTER TABLE question_revisions RENAME CONSTRAINT question_revisions_account_id_fkey TO question_revisions_account_fkey;
+ALTER TABLE question_votes RENAME CONSTRAINT question_votes_question_id_fkey TO question_votes_question_fkey;
+ALTER TABLE question_votes RENAME CONSTRAINT question_votes_account_id_fkey TO question_votes_account_fkey;
+ALTER TABLE answer_votes RENAME CONSTRAINT answer_votes_answer_id_fkey TO answer_votes_answer_fkey;
+ALTER TABLE answer_votes RENAME CONSTRAINT answer_votes_account_id_fkey TO answer_votes_account_fkey;
@@ -0,0 +1,156 @@
+use prometheus::{
+  register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge, Histogram,
+  HistogramVec, IntCounter, IntCounterVec, IntGauge,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// templates of the served paths, anything else is counted as `unmatched` to keep the label set small
+const ROUTES: &[&str] = &[
+  "/questions",
+  "/questions/{id}",
+  "/questions/{id}/answers",
+  "/questions/{id}/restore",
+  "/questions/{id}/revisions",
+  "/questions/{id}/revisions/diff",
+  "/questions/{id}/revisions/{id}/revert",
+  "/questions/{id}/vote",
+  "/tags",
+  "/answers",
+  "/answers/{id}",
+  "/answers/{id}/restore",
+  "/answers/{id}/vote",
+  "/reviews",
+  "/trash",
+  "/registration",
+  "/login",
+  "/health",
+  "/ready",
+  "/metrics",
+];
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+  register_int_counter_vec!("http_requests_total", "HTTP requests served", &["method", "route", "status"])
+    .expect("http_requests_total registered twice")
+});
+
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+  register_histogram_vec!("http_request_duration_seconds", "time spent serving HTTP requests", &["method", "route", "status"])
+    .expect("http_request_duration_seconds registered twice")
+});
+
+pub static DB_POOL_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+  register_int_gauge!("db_pool_connections", "connections open in the database pool")
+    .expect("db_pool_connections registered twice")
+});
+
+pub static DB_POOL_IDLE: LazyLock<IntGauge> = LazyLock::new(|| {
+  register_int_gauge!("db_pool_idle_connections", "open connections not in use")
+    .expect("db_pool_idle_connections registered twice")
+});
+
+static DB_POOL_ACQUIRE_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+  register_histogram!("db_pool_acquire_seconds", "time spent waiting for a connection from the database pool")
+    .expect("db_pool_acquire_seconds registered twice")
+});
+
+static DB_POOL_TIMEOUTS: LazyLock<IntCounter> = LazyLock::new(|| {
+  register_int_counter!("db_pool_timeouts_total", "queries that gave up waiting for a free connection")
+    .expect("db_pool_timeouts_total registered twice")
+});
+
+static MODERATION_CALL_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+  register_histogram_vec!("moderation_call_duration_seconds", "time spent calling the moderation service, retries included", &["outcome"])
+    .expect("moderation_call_duration_seconds registered twice")
+});
+
+static MODERATION_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+  register_int_counter_vec!("moderation_failures_total", "moderation calls that did not return censored text", &["reason"])
+    .expect("moderation_failures_total registered twice")
+});
+
+/// registers the metrics up front so they are scraped before their first use
+pub fn register() {
+  LazyLock::force(&HTTP_REQUESTS);
+  LazyLock::force(&HTTP_REQUEST_DURATION);
+  LazyLock::force(&DB_POOL_CONNECTIONS);
+  LazyLock::force(&DB_POOL_IDLE);
+  LazyLock::force(&DB_POOL_ACQUIRE_DURATION);
+  LazyLock::force(&DB_POOL_TIMEOUTS);
+  LazyLock::force(&MODERATION_CALL_DURATION);
+  LazyLock::force(&MODERATION_FAILURES);
+}
+
+/// `/questions/12/answers` becomes `/questions/{id}/answers`
+fn route_template(path: &str) -> &'static str {
+  let template = path
+    .trim_end_matches('/')
+    .split('/')
+    .map(|segment| {
+      if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+        "{id}"
+      } else {
+        segment
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("/");
+
+  ROUTES.iter().find(|route| **route == template).copied().unwrap_or("unmatched")
+}
+
+/// for `warp::log::custom`, once the reply and its status are known
+pub fn record_request(info: warp::log::Info) {
+  let status = info.status().as_u16().to_string();
+  let labels = [info.method().as_str(), route_template(info.path()), status.as_str()];
+
+  HTTP_REQUESTS.with_label_values(&labels).inc();
+  HTTP_REQUEST_DURATION.with_label_values(&labels).observe(info.elapsed().as_secs_f64());
+}
+
+pub fn observe_pool_acquire(elapsed: Duration) {
+  DB_POOL_ACQUIRE_DURATION.observe(elapsed.as_secs_f64());
+}
+
+pub fn count_pool_timeout() {
+  DB_POOL_TIMEOUTS.inc();
+}
+
+/// `outcome` is `ok` or the failure reason
+pub fn observe_moderation_call(outcome: &str, elapsed: Duration) {
+  MODERATION_CALL_DURATION.with_label_values(&[outcome]).observe(elapsed.as_secs_f64());
+  if outcome != "ok" {
+    MODERATION_FAILURES.with_label_values(&[outcome]).inc();
+  }
+}
+
+/// calls skipped because the circuit breaker is open
+pub fn count_moderation_skipped() {
+  MODERATION_FAILURES.with_label_values(&["circuit_open"]).inc();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ids_become_placeholders() {
+    assert_eq!(route_template("/questions/12"), "/questions/{id}");
+    assert_eq!(route_template("/questions/12/answers"), "/questions/{id}/answers");
+    assert_eq!(route_template("/questions/3/revisions/2/revert"), "/questions/{id}/revisions/{id}/revert");
+  }
+
+  #[test]
+  fn trailing_slashes_are_ignored() {
+    assert_eq!(route_template("/questions/"), "/questions");
+    assert_eq!(route_template("/answers/7/vote/"), "/answers/{id}/vote");
+  }
+
+  #[test]
+  fn unknown_paths_share_one_label() {
+    assert_eq!(route_template("/"), "unmatched");
+    assert_eq!(route_template("/questions/abc"), "unmatched");
+    assert_eq!(route_template("/questions/-1"), "unmatched");
+    assert_eq!(route_template("/wp-admin/login.php"), "unmatched");
+  }
+}
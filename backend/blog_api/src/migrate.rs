@@ -0,0 +1,79 @@
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// exit code used when the schema can't be migrated, `EX_DATAERR` from sysexits.h
+pub const EXIT_MIGRATION: i32 = 65;
+
+/// `undefined_table`, `_sqlx_migrations` is only created by the first `up`
+const UNDEFINED_TABLE: &str = "42P01";
+
+/// the `migrations/` directory, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// state of one migration against the database
+#[derive(Debug)]
+pub struct MigrationStatus {
+  pub version: i64,
+  pub description: String,
+  pub applied: bool,
+  /// the file changed after it was applied
+  pub checksum_mismatch: bool,
+}
+
+/// applies every pending migration
+pub async fn up(pool: &PgPool) -> Result<(), MigrateError> {
+  MIGRATOR.run(pool).await
+}
+
+/// reverts every applied migration newer than `target`,
+/// or only the latest one when no target is given
+pub async fn down(pool: &PgPool, target: Option<i64>) -> Result<(), MigrateError> {
+  let target = match target {
+    Some(target) => target,
+    None => {
+      let mut applied: Vec<i64> = status(pool)
+        .await?
+        .into_iter()
+        .filter(|migration| migration.applied)
+        .map(|migration| migration.version)
+        .collect();
+
+      // undo down to the version right before the latest one
+      applied.pop();
+      applied.pop().unwrap_or(0)
+    }
+  };
+
+  MIGRATOR.undo(pool, target).await
+}
+
+/// lists every known migration, oldest first. Only reads, so `/ready` can call it
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+  let applied = applied(pool).await?;
+
+  Ok(MIGRATOR
+    .iter()
+    .filter(|migration| !migration.migration_type.is_down_migration())
+    .map(|migration| MigrationStatus {
+      version: migration.version,
+      description: migration.description.to_string(),
+      applied: applied.contains_key(&migration.version),
+      checksum_mismatch: applied
+        .get(&migration.version)
+        .map(|checksum| checksum.as_slice() != &*migration.checksum)
+        .unwrap_or(false),
+    })
+    .collect())
+}
+
+/// checksums of the applied migrations by version, none while `_sqlx_migrations` doesn't exist
+async fn applied(pool: &PgPool) -> Result<HashMap<i64, Vec<u8>>, sqlx::Error> {
+  match sqlx::query_as::<_, (i64, Vec<u8>)>("SELECT version, checksum FROM _sqlx_migrations WHERE success ORDER BY version")
+    .fetch_all(pool)
+    .await {
+      Ok(applied) => Ok(applied.into_iter().collect()),
+      Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNDEFINED_TABLE) => Ok(HashMap::new()),
+      Err(e) => Err(e),
+    }
+}
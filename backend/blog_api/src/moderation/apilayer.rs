@@ -0,0 +1,236 @@
+use async_trait::async_trait;
+use error_handler::{ApiError, ApiLayerError};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Instant;
+use tracing::{event, Level};
+
+use crate::config::{FallbackPolicy, HttpConfig, ModerationConfig};
+use crate::metrics;
+use crate::moderation::{Moderated, Moderator};
+use crate::outbound::{CircuitBreaker, RetryPolicy};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ApiResponse {
+  message: String
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct BadWord {
+  original: String,
+  word: String,
+  deviations: i64,
+  info: i64,
+  #[serde(rename = "replaceLen")]
+  replace_len: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct BadWordsResponse {
+  content: String,
+  bad_words_total: i64,
+  bad_words_list: Vec<BadWord>,
+  censored_content: String,
+}
+
+/// client of the apilayer bad words service, `url` can point at a local mock
+pub struct ApiLayer {
+  client: reqwest::Client,
+  url: String,
+  api_key: String,
+  retries: RetryPolicy,
+  breaker: CircuitBreaker,
+  fallback: FallbackPolicy,
+}
+
+impl fmt::Debug for ApiLayer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ApiLayer")
+      .field("url", &self.url)
+      .field("api_key", &"<redacted>")
+      .field("fallback", &self.fallback)
+      .finish()
+  }
+}
+
+impl ApiLayer {
+  pub fn new(config: &ModerationConfig, http: &HttpConfig, client: reqwest::Client) -> Self {
+    ApiLayer {
+      client,
+      url: config.url.trim_end_matches('/').to_string(),
+      api_key: config.api_key.clone(),
+      retries: RetryPolicy::new(http),
+      breaker: CircuitBreaker::new(http),
+      fallback: config.fallback,
+    }
+  }
+
+  async fn call(&self, text: &str) -> Result<String, ApiError> {
+    let res = self.retries
+      .send(|| {
+        self.client
+          .post(format!("{}/bad_words?censor_character=*", self.url))
+          .header("apikey", &self.api_key)
+          .body(text.to_string())
+      })
+      .await
+      .map_err(ApiError::ExternalApiError)?;
+
+    if !res.status().is_success() {
+      let status = res.status();
+      let err = transform_error(res).await;
+      if caused_by_text(status) {
+        return Err(ApiError::ClientError(err));
+      }
+      return Err(ApiError::ServerError(err));
+    }
+
+    let res = res.json::<BadWordsResponse>().await.map_err(ApiError::ExternalApiError)?;
+    Ok(res.censored_content)
+  }
+
+  /// sends `text` and keeps the circuit breaker up to date
+  async fn call_tracked(&self, text: &str) -> Result<String, ApiError> {
+    let started = Instant::now();
+    let res = self.call(text).await;
+
+    let outcome = match &res {
+      Ok(_) => "ok",
+      Err(ApiError::ClientError(_)) => "client_error",
+      Err(ApiError::ServerError(err)) if err.status < 500 => "refused",
+      Err(ApiError::ServerError(_)) => "server_error",
+      Err(_) => "network_error",
+    };
+    metrics::observe_moderation_call(outcome, started.elapsed());
+
+    match res {
+      Ok(censored) => {
+        self.breaker.record_success();
+        Ok(censored)
+      }
+      // the service works, it only refused this text
+      Err(e @ ApiError::ClientError(_)) => {
+        self.breaker.record_success();
+        Err(e)
+      }
+      Err(e) => {
+        self.breaker.record_failure();
+        Err(e)
+      }
+    }
+  }
+
+  fn fall_back(&self, text: &str, cause: &dyn fmt::Display) -> Result<Moderated, ApiError> {
+    event!(Level::WARN, fallback = ?self.fallback, "moderation service unavailable: {}", cause);
+
+    match self.fallback {
+      FallbackPolicy::Reject => Err(ApiError::ModerationUnavailableError),
+      FallbackPolicy::Accept => Ok(Moderated::checked(text.to_string())),
+      FallbackPolicy::Queue => Ok(Moderated {
+        text: text.to_string(),
+        needs_review: true,
+      }),
+    }
+  }
+}
+
+/// 4xx replies about the text itself. Anything else, like a rate limit or a rejected api key,
+/// fails every call for a while and is handled like the service being down
+fn caused_by_text(status: StatusCode) -> bool {
+  matches!(
+    status,
+    StatusCode::BAD_REQUEST | StatusCode::PAYLOAD_TOO_LARGE | StatusCode::UNSUPPORTED_MEDIA_TYPE | StatusCode::UNPROCESSABLE_ENTITY
+  )
+}
+
+/// the error body is best effort, the service doesn't always send json
+async fn transform_error(res: reqwest::Response) -> ApiLayerError {
+  let status = res.status();
+  let body = res.text().await.unwrap_or_default();
+
+  let message = match serde_json::from_str::<ApiResponse>(&body) {
+    Ok(res) => res.message,
+    Err(_) if !body.trim().is_empty() => body,
+    Err(_) => status.canonical_reason().unwrap_or("no error message").to_string(),
+  };
+
+  ApiLayerError {
+    status: status.as_u16(),
+    message,
+  }
+}
+
+#[async_trait]
+impl Moderator for ApiLayer {
+  async fn censor(&self, text: &str) -> Result<Moderated, ApiError> {
+    // the service rejects empty bodies
+    if text.trim().is_empty() {
+      return Ok(Moderated::checked(text.to_string()));
+    }
+
+    if !self.breaker.allow() {
+      metrics::count_moderation_skipped();
+      return self.fall_back(text, &"circuit breaker open");
+    }
+
+    match self.call_tracked(text).await {
+      Ok(censored) => Ok(Moderated::checked(censored)),
+      Err(e @ ApiError::ClientError(_)) => Err(e),
+      Err(e) => self.fall_back(text, &e),
+    }
+  }
+
+  async fn check(&self) -> Result<(), ApiError> {
+    if !self.breaker.allow() {
+      return Err(ApiError::ModerationUnavailableError);
+    }
+
+    self.call_tracked("readiness check").await.map(|_| ())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::outbound;
+  use crate::testing::mock_service;
+  use std::sync::atomic::Ordering;
+
+  fn api_layer(url: String, fallback: FallbackPolicy) -> ApiLayer {
+    let config = ModerationConfig { url, api_key: "key".to_string(), fallback, ..ModerationConfig::default() };
+    let http = HttpConfig { max_retries: 0, breaker_threshold: 2, ..HttpConfig::default() };
+    ApiLayer::new(&config, &http, outbound::client(&http))
+  }
+
+  #[tokio::test]
+  async fn rate_limits_fall_back_and_open_the_breaker() {
+    let (url, calls) = mock_service(429).await;
+    let moderator = api_layer(url, FallbackPolicy::Queue);
+
+    for _ in 0..3 {
+      let moderated = moderator.censor("some text").await.unwrap();
+      assert_eq!((moderated.text.as_str(), moderated.needs_review), ("some text", true));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+  }
+
+  #[tokio::test]
+  async fn rejected_api_keys_count_as_unavailable() {
+    let (url, _) = mock_service(401).await;
+    let moderator = api_layer(url, FallbackPolicy::Reject);
+
+    assert!(matches!(moderator.censor("some text").await, Err(ApiError::ModerationUnavailableError)));
+  }
+
+  #[tokio::test]
+  async fn texts_the_service_refuses_fail_without_opening_the_breaker() {
+    let (url, calls) = mock_service(400).await;
+    let moderator = api_layer(url, FallbackPolicy::Accept);
+
+    for _ in 0..3 {
+      assert!(matches!(moderator.censor("some text").await, Err(ApiError::ClientError(_))));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+  }
+}
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use error_handler::ApiError;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::config::{HttpConfig, ModerationBackend, ModerationConfig};
+
+pub mod apilayer;
+pub mod word_list;
+
+/// checks user supplied text before it is stored
+#[async_trait]
+pub trait Moderator: Debug + Send + Sync {
+  /// `text` with every offending word masked
+  async fn censor(&self, text: &str) -> Result<Moderated, ApiError>;
+
+  /// fails while moderation can't be done, for `/ready`
+  async fn check(&self) -> Result<(), ApiError> {
+    Ok(())
+  }
+}
+
+/// text as it should be stored
+#[derive(Debug)]
+pub struct Moderated {
+  pub text: String,
+  /// the text is unmoderated and waits for a human to look at it
+  pub needs_review: bool,
+}
+
+impl Moderated {
+  pub fn checked(text: String) -> Self {
+    Moderated {
+      text,
+      needs_review: false,
+    }
+  }
+}
+
+/// moderator shared by every request
+pub type SharedModerator = Arc<dyn Moderator>;
+
+/// builds the moderator selected by `moderation.backend`
+pub fn from_config(config: &ModerationConfig, http: &HttpConfig, client: reqwest::Client) -> SharedModerator {
+  match config.backend {
+    ModerationBackend::Apilayer => Arc::new(apilayer::ApiLayer::new(config, http, client)),
+    ModerationBackend::WordList => Arc::new(word_list::WordList::new(&config.words)),
+    ModerationBackend::None => Arc::new(NoModeration),
+  }
+}
+
+/// stores text as it was sent
+#[derive(Debug)]
+pub struct NoModeration;
+
+#[async_trait]
+impl Moderator for NoModeration {
+  async fn censor(&self, text: &str) -> Result<Moderated, ApiError> {
+    Ok(Moderated::checked(text.to_string()))
+  }
+}
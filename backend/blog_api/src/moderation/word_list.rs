@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use error_handler::ApiError;
+use std::collections::HashSet;
+
+use crate::moderation::{Moderated, Moderator};
+
+/// masks words found in a fixed list, case insensitive and without network access
+#[derive(Debug, Clone)]
+pub struct WordList {
+  words: HashSet<String>,
+}
+
+impl WordList {
+  pub fn new(words: &[String]) -> Self {
+    WordList {
+      words: words.iter().map(|word| word.to_lowercase()).collect(),
+    }
+  }
+
+  fn mask(&self, text: &str) -> String {
+    let mut censored = String::with_capacity(text.len());
+    let mut word = String::new();
+
+    for c in text.chars().chain(std::iter::once(' ')) {
+      if c.is_alphanumeric() {
+        word.push(c);
+        continue;
+      }
+
+      if self.words.contains(&word.to_lowercase()) {
+        censored.extend(word.chars().map(|_| '*'));
+      } else {
+        censored.push_str(&word);
+      }
+      word.clear();
+      censored.push(c);
+    }
+
+    // drop the space chained on to flush the last word
+    censored.pop();
+    censored
+  }
+}
+
+#[async_trait]
+impl Moderator for WordList {
+  async fn censor(&self, text: &str) -> Result<Moderated, ApiError> {
+    Ok(Moderated::checked(self.mask(text)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn list(words: &[&str]) -> WordList {
+    WordList::new(&words.iter().map(|word| word.to_string()).collect::<Vec<_>>())
+  }
+
+  #[test]
+  fn matches_ignore_case_on_both_sides() {
+    let words = list(&["Darn"]);
+    assert_eq!(words.mask("darn it"), "**** it");
+    assert_eq!(words.mask("DARN it"), "**** it");
+    assert_eq!(words.mask("DaRn it"), "**** it");
+  }
+
+  #[test]
+  fn only_whole_words_are_masked() {
+    let words = list(&["darn"]);
+    assert_eq!(words.mask("darned darnit undarn"), "darned darnit undarn");
+    assert_eq!(words.mask("darn, darn! (darn) darn-darn"), "****, ****! (****) ****-****");
+    assert_eq!(words.mask("darn_it"), "****_it");
+  }
+
+  #[test]
+  fn whitespace_and_punctuation_are_kept() {
+    let words = list(&["darn"]);
+    assert_eq!(words.mask("  darn\n\tdarn  "), "  ****\n\t****  ");
+    assert_eq!(words.mask(""), "");
+  }
+
+  #[test]
+  fn masks_keep_the_length_in_characters() {
+    let words = list(&["grüße"]);
+    assert_eq!(words.mask("viele GRÜSSE, viele Grüße"), "viele GRÜSSE, viele *****");
+  }
+}
@@ -0,0 +1,201 @@
+use parking_lot::Mutex;
+use rand::Rng;
+use std::time::{Duration, Instant};
+use tracing::{event, Level};
+
+use crate::config::HttpConfig;
+
+/// client shared by every outbound call, cheap to clone
+pub fn client(config: &HttpConfig) -> reqwest::Client {
+  reqwest::Client::builder()
+    .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+    // reqwest 0.11 has no separate read timeout, this bounds the whole call
+    .timeout(Duration::from_millis(config.read_timeout_ms))
+    .build()
+    .expect("cannot initialise the TLS backend")
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+  pub max_retries: u32,
+  pub backoff: Duration,
+}
+
+impl RetryPolicy {
+  pub fn new(config: &HttpConfig) -> Self {
+    RetryPolicy {
+      max_retries: config.max_retries,
+      backoff: Duration::from_millis(config.retry_backoff_ms),
+    }
+  }
+
+  /// random delay up to `backoff * 2^attempt`, so clients that failed together don't retry together
+  fn delay(&self, attempt: u32) -> Duration {
+    let ceiling = self.backoff.saturating_mul(2u32.saturating_pow(attempt));
+    ceiling.mul_f64(rand::thread_rng().gen::<f64>())
+  }
+
+  /// sends the request built by `request`, retrying network errors and 5xx replies.
+  /// The last reply is returned once retries run out, even when it is a 5xx.
+  pub async fn send<F>(&self, request: F) -> Result<reqwest::Response, reqwest::Error>
+  where
+    F: Fn() -> reqwest::RequestBuilder,
+  {
+    let mut attempt = 0;
+
+    loop {
+      let res = request().send().await;
+      let retry = match &res {
+        Ok(res) => res.status().is_server_error(),
+        Err(e) => e.is_connect() || e.is_timeout(),
+      };
+
+      if !retry || attempt >= self.max_retries {
+        return res;
+      }
+
+      let delay = self.delay(attempt);
+      event!(Level::WARN, attempt = attempt + 1, delay_ms = delay.as_millis() as u64, "retrying outbound call");
+      tokio::time::sleep(delay).await;
+      attempt += 1;
+    }
+  }
+}
+
+/// stops calling a service after `threshold` failures in a row.
+/// Once `cooldown` has passed a single call is let through, and its outcome
+/// closes the breaker again or keeps it open for another cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+  threshold: u32,
+  cooldown: Duration,
+  state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+  failures: u32,
+  open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+  pub fn new(config: &HttpConfig) -> Self {
+    CircuitBreaker {
+      threshold: config.breaker_threshold,
+      cooldown: Duration::from_secs(config.breaker_cooldown),
+      state: Mutex::new(BreakerState::default()),
+    }
+  }
+
+  /// whether a call may go out now
+  pub fn allow(&self) -> bool {
+    let mut state = self.state.lock();
+
+    match state.open_until {
+      None => true,
+      Some(until) if Instant::now() >= until => {
+        // this call probes the service, everyone else waits for another cooldown
+        state.open_until = Some(Instant::now() + self.cooldown);
+        true
+      }
+      Some(_) => false,
+    }
+  }
+
+  pub fn record_success(&self) {
+    let mut state = self.state.lock();
+    state.failures = 0;
+    state.open_until = None;
+  }
+
+  pub fn record_failure(&self) {
+    let mut state = self.state.lock();
+    state.failures += 1;
+
+    if state.failures >= self.threshold {
+      if state.open_until.is_none() {
+        event!(Level::WARN, failures = state.failures, "opening circuit breaker");
+      }
+      state.open_until = Some(Instant::now() + self.cooldown);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::mock_service;
+  use std::sync::atomic::Ordering;
+
+  fn breaker(threshold: u32, cooldown: Duration) -> CircuitBreaker {
+    CircuitBreaker { threshold, cooldown, state: Mutex::new(BreakerState::default()) }
+  }
+
+  #[test]
+  fn delays_stay_below_the_doubled_backoff() {
+    let policy = RetryPolicy { max_retries: 3, backoff: Duration::from_millis(100) };
+
+    for attempt in 0..5 {
+      assert!(policy.delay(attempt) <= Duration::from_millis(100 * 2u64.pow(attempt)));
+    }
+    // the ceiling saturates instead of overflowing
+    assert!(policy.delay(64) <= Duration::from_millis(100).saturating_mul(u32::MAX));
+  }
+
+  #[tokio::test]
+  async fn server_errors_are_retried_until_retries_run_out() {
+    let (url, calls) = mock_service(503).await;
+    let policy = RetryPolicy { max_retries: 2, backoff: Duration::from_millis(1) };
+    let client = reqwest::Client::new();
+
+    let res = policy.send(|| client.get(&url)).await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+  }
+
+  #[tokio::test]
+  async fn client_errors_are_not_retried() {
+    let (url, calls) = mock_service(400).await;
+    let policy = RetryPolicy { max_retries: 2, backoff: Duration::from_millis(1) };
+    let client = reqwest::Client::new();
+
+    policy.send(|| client.get(&url)).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn breakers_open_after_the_threshold() {
+    let breaker = breaker(2, Duration::from_secs(60));
+
+    breaker.record_failure();
+    assert!(breaker.allow());
+    breaker.record_success();
+    breaker.record_failure();
+    assert!(breaker.allow(), "a success resets the count");
+
+    breaker.record_failure();
+    assert!(!breaker.allow());
+  }
+
+  #[test]
+  fn open_breakers_let_one_probe_through_after_the_cooldown() {
+    let breaker = breaker(1, Duration::from_millis(20));
+    breaker.record_failure();
+    assert!(!breaker.allow());
+
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(breaker.allow(), "half open, the probe goes out");
+    assert!(!breaker.allow(), "everyone else waits for the probe");
+
+    // a failed probe keeps it open for another cooldown
+    breaker.record_failure();
+    assert!(!breaker.allow());
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(breaker.allow());
+
+    // a successful probe closes it
+    breaker.record_success();
+    assert!(breaker.allow());
+    assert!(breaker.allow());
+  }
+}
@@ -1,20 +1,140 @@
+use crate::moderation::SharedModerator;
+use crate::routes::authentication::authorize;
 use crate::store::Store;
-use crate::types::answer::{Answer, AnswerId};
+use crate::types::account::Session;
+use crate::types::answer::{AnswerUpdate, NewAnswer};
+use crate::types::pagination::extract_capped_pagination;
 use crate::types::question::QuestionId;
+use error_handler::ApiError;
 use std::collections::HashMap;
 use warp::hyper::StatusCode;
 
-pub async fn add_anwer(
-  store: Store,
+pub async fn get_answers<S: Store>(
+  question_id: i32,
   params: HashMap<String, String>,
+  store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-  let answer = Answer {
-    id: AnswerId(String::from("1234")),
-    question_id: QuestionId(params.get("relationId").unwrap().parse().unwrap()),
-    content: String::from(params.get("content").unwrap()),
+  let pagination = extract_capped_pagination(&params)?;
+
+  match store.get_answers(question_id, pagination.limit, pagination.offset).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn add_answer<S: Store>(
+  session: Session,
+  store: S,
+  moderator: SharedModerator,
+  params: HashMap<String, String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let question_id = params
+    .get("relationId")
+    .ok_or(ApiError::MissingParamError)?
+    .parse()
+    .map_err(ApiError::ParseError)?;
+
+  let content = params
+    .get("content")
+    .ok_or(ApiError::MissingParamError)?;
+
+  let content = moderator.censor(content).await?;
+  let answer = NewAnswer {
+    content: content.text,
+    question_id: QuestionId(question_id),
   };
 
-  // store.answers.write().insert(answer.id.clone(), answer);
+  match store.add_answer(answer, session.account_id, content.needs_review).await {
+    Ok(answer) => Ok(warp::reply::json(&answer)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn update_answer<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+  moderator: SharedModerator,
+  update: AnswerUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let owner = store.get_answer_owner(id).await?;
+  authorize(&store, &session, owner).await?;
+  let content = moderator.censor(&update.content).await?;
+  let needs_review = content.needs_review;
+  let update = AnswerUpdate {
+    content: content.text,
+  };
+
+  match store.update_answer(update, id, needs_review).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn delete_answer<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let owner = store.get_answer_owner(id).await?;
+  authorize(&store, &session, owner).await?;
+
+  match store.delete_answer(id).await {
+    Ok(_) => Ok(warp::reply::with_status(format!("answer {} deleted", id), StatusCode::OK)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::{authorized, post_answer, TestApi};
+  use crate::types::pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+  use serde_json::json;
+  use warp::http::StatusCode;
+
+  #[tokio::test]
+  async fn answers_need_an_existing_question() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+
+    let (status, problem) = api.send(post_answer(&token, 999)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "question_not_found");
+
+    let (status, problem) = api.send(warp::test::request().path("/questions/999/answers")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "question_not_found");
+
+    let id = api.ask(&token, "asked").await;
+    let (status, answer) = api.send(post_answer(&token, id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(answer["question_id"], id);
+
+    let (status, answers) = api.send(warp::test::request().path(&format!("/questions/{}/answers", id))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(answers.as_array().map(Vec::len), Some(1));
+
+    let (status, problem) = api.send(authorized("PUT", "/answers/999", &token).json(&json!({ "content": "edited" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "answer_not_found");
+  }
+
+  #[tokio::test]
+  async fn answer_pages_are_capped_like_question_pages() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+    let id = api.ask(&token, "popular").await;
+    for _ in 0..(MAX_PAGE_SIZE + 1) {
+      api.answer(&token, id).await;
+    }
 
-  Ok(warp::reply::with_status("answer added", StatusCode::OK))
+    let count = |query: &str| {
+      let request = warp::test::request().path(&format!("/questions/{}/answers{}", id, query));
+      async { api.send(request).await.1.as_array().map(Vec::len) }
+    };
+    assert_eq!(count("").await, Some(DEFAULT_PAGE_SIZE as usize));
+    assert_eq!(count("?limit=5").await, Some(5));
+    assert_eq!(count("?limit=1000").await, Some(MAX_PAGE_SIZE as usize));
+    assert_eq!(count("?offset=100&unrelated=1").await, Some(1));
+  }
 }
@@ -0,0 +1,188 @@
+use crate::store::Store;
+use crate::types::account::{Account, AccountId, AuthToken, Role, Session};
+use argon2::Config;
+use chrono::{Duration, Utc};
+use error_handler::ApiError;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
+use std::fmt;
+use std::sync::LazyLock;
+use warp::Filter;
+
+/// checked instead of a stored hash when the email is unknown,
+/// so a missing account takes as long to reject as a wrong password
+static DUMMY_HASH: LazyLock<String> =
+  LazyLock::new(|| hash(b"dummy password for unknown accounts").expect("hashing with the default config works"));
+
+/// secret and lifetime of the bearer tokens handed out on login
+#[derive(Clone)]
+pub struct TokenSettings {
+  pub secret: String,
+  pub ttl: Duration,
+}
+
+impl fmt::Debug for TokenSettings {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("TokenSettings")
+      .field("secret", &"<redacted>")
+      .field("ttl", &self.ttl)
+      .finish()
+  }
+}
+
+pub async fn register<S: Store>(
+  store: S,
+  account: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  if !account.email.contains('@') {
+    return Err(warp::reject::custom(ApiError::InvalidParamError("email is not valid".to_string())));
+  }
+
+  if account.password.len() < 8 {
+    return Err(warp::reject::custom(ApiError::InvalidParamError("password needs at least 8 characters".to_string())));
+  }
+
+  let account = Account {
+    id: None,
+    email: account.email,
+    password: hash_password(account.password).await?,
+  };
+
+  match store.add_account(account).await {
+    Ok(account) => Ok(warp::reply::json(&account)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn login<S: Store>(
+  store: S,
+  tokens: TokenSettings,
+  login: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let account = match store.get_account(&login.email).await {
+    Ok(Some(account)) => account,
+    Ok(None) => {
+      // dereferenced on the blocking pool too, the first use hashes
+      blocking(move || verify(&DUMMY_HASH, login.password.as_bytes())).await?;
+      return Err(warp::reject::custom(ApiError::WrongPasswordError));
+    }
+    Err(e) => return Err(warp::reject::custom(e)),
+  };
+
+  if !verify_password(account.password, login.password).await? {
+    return Err(warp::reject::custom(ApiError::WrongPasswordError));
+  }
+
+  let account_id = account.id.ok_or(ApiError::WrongPasswordError)?;
+  Ok(warp::reply::json(&issue_token(&tokens, account_id)?))
+}
+
+/// extracts the session from an `Authorization: Bearer <token>` header,
+/// rejecting with `UnauthorizedError` when it is missing, malformed or expired
+pub fn auth(tokens: TokenSettings) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+  warp::header::optional::<String>("Authorization").and_then(move |header: Option<String>| {
+    let tokens = tokens.clone();
+
+    async move {
+      let token = header
+        .as_deref()
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or(ApiError::UnauthorizedError)?;
+
+      verify_token(&tokens, token).map_err(warp::reject::custom)
+    }
+  })
+}
+
+/// lets the author of an item or an admin through, anyone else gets a `ForbiddenError`
+pub async fn authorize<S: Store>(store: &S, session: &Session, owner: Option<AccountId>) -> Result<(), ApiError> {
+  if owner == Some(session.account_id) {
+    return Ok(());
+  }
+
+  match store.get_role(session.account_id).await? {
+    Role::Admin => Ok(()),
+    Role::User => Err(ApiError::ForbiddenError),
+  }
+}
+
+async fn hash_password(password: String) -> Result<String, ApiError> {
+  blocking(move || hash(password.as_bytes())).await
+}
+
+async fn verify_password(hash: String, password: String) -> Result<bool, ApiError> {
+  blocking(move || verify(&hash, password.as_bytes())).await
+}
+
+/// argon2 is slow on purpose, it runs on the blocking pool so other requests keep being served
+async fn blocking<T, F>(f: F) -> Result<T, ApiError>
+where
+  T: Send + 'static,
+  F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+{
+  tokio::task::spawn_blocking(f).await.map_err(ApiError::BlockingTaskError)?
+}
+
+fn hash(password: &[u8]) -> Result<String, ApiError> {
+  let salt = rand::thread_rng().gen::<[u8; 32]>();
+  argon2::hash_encoded(password, &salt, &Config::default()).map_err(ApiError::ArgonLibraryError)
+}
+
+fn verify(hash: &str, password: &[u8]) -> Result<bool, ApiError> {
+  argon2::verify_encoded(hash, password).map_err(ApiError::ArgonLibraryError)
+}
+
+fn issue_token(tokens: &TokenSettings, account_id: AccountId) -> Result<AuthToken, ApiError> {
+  let now = Utc::now();
+  let expires_at = now + tokens.ttl;
+
+  let session = Session {
+    account_id,
+    exp: expires_at.timestamp(),
+    nbf: now.timestamp(),
+  };
+
+  match encode(&Header::default(), &session, &EncodingKey::from_secret(tokens.secret.as_bytes())) {
+    Ok(token) => Ok(AuthToken { token, expires_at: expires_at.naive_utc() }),
+    Err(e) => {
+      tracing::event!(tracing::Level::ERROR, "could not sign token: {:?}", e);
+      Err(ApiError::UnauthorizedError)
+    }
+  }
+}
+
+fn verify_token(tokens: &TokenSettings, token: &str) -> Result<Session, ApiError> {
+  let mut validation = Validation::default();
+  validation.validate_nbf = true;
+
+  match decode::<Session>(token, &DecodingKey::from_secret(tokens.secret.as_bytes()), &validation) {
+    Ok(data) => Ok(data.claims),
+    Err(e) => {
+      tracing::event!(tracing::Level::WARN, "rejected token: {:?}", e);
+      Err(ApiError::UnauthorizedError)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dummy_hash_is_a_real_argon_hash() {
+    assert_eq!(verify(&DUMMY_HASH, b"password1").ok(), Some(false));
+  }
+
+  #[tokio::test]
+  async fn passwords_verify_against_their_hash() {
+    let hash = hash_password("password1".to_string()).await.unwrap();
+    assert!(verify_password(hash.clone(), "password1".to_string()).await.unwrap());
+    assert!(!verify_password(hash, "password2".to_string()).await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn panics_while_hashing_become_errors() {
+    let res: Result<(), ApiError> = blocking(|| panic!("hashing failed")).await;
+    assert!(matches!(res, Err(ApiError::BlockingTaskError(_))));
+  }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use warp::http::header::{CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use warp::http::Response;
+use warp::hyper::{Body, StatusCode};
+use warp::Filter;
+
+/// validators sent along with a conditional GET
+#[derive(Debug, Default)]
+pub struct Conditions {
+  pub if_none_match: Option<String>,
+  pub if_modified_since: Option<String>,
+}
+
+/// extracts the `If-None-Match` and `If-Modified-Since` headers
+pub fn conditions() -> impl Filter<Extract = (Conditions,), Error = warp::Rejection> + Clone {
+  warp::header::optional::<String>("if-none-match")
+    .and(warp::header::optional::<String>("if-modified-since"))
+    .map(|if_none_match, if_modified_since| Conditions {
+      if_none_match,
+      if_modified_since,
+    })
+}
+
+/// replies with `value` as json tagged with `ETag` and `Last-Modified`,
+/// or with an empty `304 Not Modified` when the client copy is still fresh.
+/// Lists pass no `last_modified`: removed rows don't show up in the newest `updated_at`
+pub fn conditional_json<T: Serialize>(
+  value: &T,
+  last_modified: Option<NaiveDateTime>,
+  conditions: &Conditions,
+) -> warp::reply::Response {
+  let body = serde_json::to_vec(value).unwrap_or_default();
+
+  let etag = etag(&body);
+
+  let fresh = match (&conditions.if_none_match, &conditions.if_modified_since) {
+    // If-Modified-Since is ignored whenever If-None-Match is present
+    (Some(if_none_match), _) => if_none_match
+      .split(',')
+      .map(|tag| tag.trim().trim_start_matches("W/"))
+      .any(|tag| tag == "*" || tag == etag),
+    (None, Some(if_modified_since)) => match (last_modified, DateTime::parse_from_rfc2822(if_modified_since)) {
+      // http dates only carry whole seconds
+      (Some(modified), Ok(since)) => modified.timestamp() <= since.timestamp(),
+      _ => false,
+    },
+    (None, None) => false,
+  };
+
+  let mut response = Response::builder().header(ETAG, &etag);
+  if let Some(modified) = last_modified {
+    response = response.header(LAST_MODIFIED, modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+  }
+
+  let response = if fresh {
+    response.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+  } else {
+    response
+      .status(StatusCode::OK)
+      .header(CONTENT_TYPE, "application/json")
+      .body(Body::from(body))
+  };
+
+  response.unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+/// strong validator over the serialized body, the same across builds and releases
+fn etag(body: &[u8]) -> String {
+  let digest = Sha256::digest(body);
+  let hex: String = digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+  format!("\"{}\"", hex)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn etags_are_stable_sha256_prefixes() {
+    assert_eq!(etag(b"[]"), "\"4f53cda18c2baa0c0354bb5f9a3ecbe5\"");
+    assert_ne!(etag(b"[]"), etag(b"[1]"));
+  }
+
+  #[test]
+  fn lists_only_carry_an_etag() {
+    let res = conditional_json(&vec![1, 2], None, &Conditions::default());
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().contains_key(ETAG));
+    assert!(!res.headers().contains_key(LAST_MODIFIED));
+  }
+
+  #[test]
+  fn matching_etags_are_not_modified() {
+    let etag = etag(&serde_json::to_vec(&vec![1, 2]).unwrap());
+    let conditions = Conditions { if_none_match: Some(format!("W/{}", etag)), if_modified_since: None };
+
+    assert_eq!(conditional_json(&vec![1, 2], None, &conditions).status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(conditional_json(&vec![1, 3], None, &conditions).status(), StatusCode::OK);
+  }
+
+  #[test]
+  fn if_modified_since_is_ignored_without_a_date() {
+    let conditions = Conditions { if_none_match: None, if_modified_since: Some("Wed, 01 Jan 2099 00:00:00 GMT".to_string()) };
+    assert_eq!(conditional_json(&vec![1], None, &conditions).status(), StatusCode::OK);
+  }
+}
@@ -0,0 +1,120 @@
+use error_handler::ApiError;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use warp::hyper::StatusCode;
+
+use crate::moderation::SharedModerator;
+use crate::store::Store;
+
+/// longest a single dependency check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// how far startup got, the database can still go away afterwards
+#[derive(Debug, Clone, Default)]
+pub struct Readiness(Arc<RwLock<Startup>>);
+
+#[derive(Debug, Clone, Default)]
+enum Startup {
+  #[default]
+  Waiting,
+  Ready,
+  /// migrations failed after the server was already listening
+  Failed(String),
+}
+
+impl Readiness {
+  pub fn set_ready(&self) {
+    *self.0.write() = Startup::Ready;
+  }
+
+  /// keeps the server up but not ready, with `error` shown on `/ready`
+  pub fn set_failed(&self, error: impl ToString) {
+    *self.0.write() = Startup::Failed(error.to_string());
+  }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+  Ok,
+  Failed,
+}
+
+#[derive(Serialize)]
+struct Check {
+  status: Status,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pending: Option<Vec<i64>>,
+}
+
+impl Check {
+  fn ok() -> Self {
+    Check { status: Status::Ok, error: None, pending: None }
+  }
+
+  fn failed(error: impl ToString) -> Self {
+    Check { status: Status::Failed, error: Some(error.to_string()), pending: None }
+  }
+}
+
+#[derive(Serialize)]
+struct ReadyResponse {
+  ready: bool,
+  checks: BTreeMap<&'static str, Check>,
+}
+
+/// the process is up, says nothing about its dependencies
+pub async fn health() -> Result<impl warp::Reply, warp::Rejection> {
+  Ok(warp::reply::json(&serde_json::json!({ "status": "ok" })))
+}
+
+/// checks every dependency and replies 200 when all of them are fine, 503 otherwise.
+/// `moderator` is only given when `moderation.check_on_ready` is set.
+pub async fn ready<S: Store>(
+  readiness: Readiness,
+  store: S,
+  moderator: Option<SharedModerator>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let mut checks = BTreeMap::new();
+
+  let startup = readiness.0.read().clone();
+  match startup {
+    Startup::Ready => {
+      checks.insert("database", timed(store.ping()).await.map_or_else(Check::failed, |_| Check::ok()));
+      checks.insert("migrations", match timed(store.pending_migrations()).await {
+        Ok(pending) if pending.is_empty() => Check::ok(),
+        Ok(pending) => Check { pending: Some(pending), ..Check::failed("migrations not applied") },
+        Err(e) => Check::failed(e),
+      });
+    }
+    Startup::Failed(error) => {
+      checks.insert("database", timed(store.ping()).await.map_or_else(Check::failed, |_| Check::ok()));
+      checks.insert("migrations", Check::failed(error));
+    }
+    Startup::Waiting => {
+      checks.insert("database", Check::failed("waiting for the database"));
+    }
+  }
+
+  if let Some(moderator) = moderator {
+    checks.insert("moderation", timed(moderator.check()).await.map_or_else(Check::failed, |_| Check::ok()));
+  }
+
+  let ready = checks.values().all(|check| check.status == Status::Ok);
+  let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+  Ok(warp::reply::with_status(warp::reply::json(&ReadyResponse { ready, checks }), status))
+}
+
+async fn timed<T>(check: impl Future<Output = Result<T, ApiError>>) -> Result<T, String> {
+  match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+    Ok(res) => res.map_err(|e| e.to_string()),
+    Err(_) => Err(format!("no answer within {:?}", CHECK_TIMEOUT)),
+  }
+}
@@ -0,0 +1,23 @@
+use prometheus::{Encoder, TextEncoder};
+use warp::hyper::StatusCode;
+use warp::Reply;
+
+use crate::metrics::{DB_POOL_CONNECTIONS, DB_POOL_IDLE};
+use crate::store::Store;
+
+/// every registered metric in the prometheus text format
+pub async fn metrics<S: Store>(store: S) -> Result<impl warp::Reply, warp::Rejection> {
+  if let Some(stats) = store.pool_stats() {
+    DB_POOL_CONNECTIONS.set(stats.size as i64);
+    DB_POOL_IDLE.set(stats.idle as i64);
+  }
+
+  let encoder = TextEncoder::new();
+  match encoder.encode_to_string(&prometheus::gather()) {
+    Ok(body) => Ok(warp::reply::with_header(body, "content-type", encoder.format_type()).into_response()),
+    Err(e) => {
+      tracing::event!(tracing::Level::ERROR, "cannot encode metrics: {}", e);
+      Ok(warp::reply::with_status("cannot encode metrics".to_string(), StatusCode::INTERNAL_SERVER_ERROR).into_response())
+    }
+  }
+}
@@ -1,2 +1,11 @@
 pub mod answer;
+pub mod authentication;
+pub mod conditional;
+pub mod health;
+pub mod metrics;
 pub mod question;
+pub mod review;
+pub mod revision;
+pub mod tag;
+pub mod trash;
+pub mod vote;
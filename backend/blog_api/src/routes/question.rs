@@ -1,116 +1,321 @@
-use error_handler::ApiLayerError;
-use serde::{Deserialize, Serialize};
+use error_handler::ApiError;
 use tracing::Level;
 use tracing::{instrument, event};
 use warp::hyper::StatusCode;
 use std::collections::HashMap;
+use crate::config::OnQuestionDelete;
+use crate::moderation::SharedModerator;
+use crate::routes::authentication::authorize;
+use crate::routes::conditional::{conditional_json, Conditions};
 use crate::store::Store;
-use crate::types::pagination::{extract_pagination, Pagination};
-use crate::types::question::{Question, NewQuestion};
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct ApiResponse {
-  message: String
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct BadWord {
-  original: String,
-  word: String,
-  deviations: i64,
-  info: i64,
-  #[serde(rename = "replaceLen")]
-  replace_len: i64,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct BadWordsResponse {
-  content: String,
-  bad_words_total: i64,
-  bad_words_list: Vec<BadWord>,
-  censored_content: String,
-}
+use crate::types::account::Session;
+use crate::types::filter::{extract_filter, extract_question_sort};
+use crate::types::pagination::{extract_cursor_pagination, extract_pagination};
+use crate::types::question::{QuestionId, QuestionPatch, NewQuestion};
 
 #[instrument]
-pub async fn get_questions(
+pub async fn get_questions<S: Store>(
   params: HashMap<String, String>,
-  store: Store,
+  conditions: Conditions,
+  store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
   event!(target: "blog_api", Level::INFO, "querying questions");
-  let mut pagination = Pagination::default();
+  let filter = extract_filter(&params);
+  let sort = extract_question_sort(&params)?;
+
+  // the cursor page is opt-in, plain and `offset` requests keep getting a bare list
+  if params.contains_key("offset") {
+    event!(Level::INFO, pagination = "offset");
+    let pagination = extract_pagination(params)?;
 
-  if !params.is_empty() {
-    event!(Level::INFO, pagination = true);
-    pagination = extract_pagination(params)?;
+    return match store.get_questions(pagination.limit, pagination.offset, filter, sort).await {
+      Ok(res) => Ok(conditional_json(&res, None, &conditions)),
+      Err(e) => Err(warp::reject::custom(e)),
+    };
   }
 
-  match store.get_questions(pagination.limit, pagination.offset).await {
-    Ok(res) => Ok(warp::reply::json(&res)),
-    Err(e) => return Err(warp::reject::custom(e)),
+  if !params.contains_key("cursor") && !params.contains_key("limit") {
+    event!(Level::INFO, pagination = "none");
+
+    return match store.get_questions(None, 0, filter, sort).await {
+      Ok(res) => Ok(conditional_json(&res, None, &conditions)),
+      Err(e) => Err(warp::reject::custom(e)),
+    };
   }
-}
 
-async fn transform_error(res: reqwest::Response) -> ApiLayerError {
-  ApiLayerError {
-    status: res.status().as_u16(),
-    message: res.json::<ApiResponse>().await.unwrap().message,
+  event!(Level::INFO, pagination = "cursor");
+  let pagination = extract_cursor_pagination(&params)?;
+
+  match store.get_questions_page(pagination, filter, sort).await {
+    Ok(res) => Ok(conditional_json(&res, None, &conditions)),
+    Err(e) => Err(warp::reject::custom(e)),
   }
 }
 
-pub async fn add_question(
-  store: Store,
-  new_question: NewQuestion,
+pub async fn get_question<S: Store>(
+  id: i32,
+  conditions: Conditions,
+  store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-  let client = reqwest::Client::new();
-  let res = client.post("https://api.apilayer.com/bad_words?censor_character=*")
-    .header("apikey", "Some api key")
-    .body("a list of shit words")
-    .send()
-    .await
-    .map_err(|e| error_handler::ApiError::ExternalApiError(e))?;
-
-
-  if !res.status().is_success() {
-    if res.status().is_client_error() {
-      let err = transform_error(res).await;
-      return Err(error_handler::ApiError::ClientError(err).into())
-    }
-
-    let err = transform_error(res).await;
-    return Err(error_handler::ApiError::ServerError(err).into())
+  match store.get_question(id).await {
+    Ok(res) => Ok(conditional_json(&res, Some(res.updated_at), &conditions)),
+    Err(e) => Err(warp::reject::custom(e)),
   }
+}
 
-  let res = res.json::<BadWordsResponse>().await.map_err(|e| error_handler::ApiError::ExternalApiError(e))?;
+/// runs title, content and every tag through the moderator.
+/// The flag is set when any part was stored unmoderated.
+async fn moderate(moderator: &SharedModerator, question: NewQuestion) -> Result<(NewQuestion, bool), ApiError> {
+  let title = moderator.censor(&question.title).await?;
+  let content = moderator.censor(&question.content).await?;
+  let (tags, tags_need_review) = moderate_tags(moderator, question.tags).await?;
+  let needs_review = title.needs_review || content.needs_review || tags_need_review;
+
+  Ok((NewQuestion { title: title.text, content: content.text, tags }, needs_review))
+}
 
-  let question = NewQuestion {
-    title: new_question.title,
-    content: res.censored_content,
-    tags: new_question.tags
+/// tags are shown and searched like any other text, so they are censored one by one
+async fn moderate_tags(moderator: &SharedModerator, tags: Option<Vec<String>>) -> Result<(Option<Vec<String>>, bool), ApiError> {
+  let tags = match tags {
+    Some(tags) => tags,
+    None => return Ok((None, false)),
   };
 
-  match store.add_question(question).await {
+  let mut censored = Vec::with_capacity(tags.len());
+  let mut needs_review = false;
+  for tag in &tags {
+    let tag = moderator.censor(tag).await?;
+    needs_review |= tag.needs_review;
+    censored.push(tag.text);
+  }
+  Ok((Some(censored), needs_review))
+}
+
+pub async fn add_question<S: Store>(
+  session: Session,
+  store: S,
+  moderator: SharedModerator,
+  new_question: NewQuestion,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let (question, needs_review) = moderate(&moderator, new_question).await?;
+
+  match store.add_question(question, session.account_id, needs_review).await {
     Ok(question) => Ok(warp::reply::json(&question)),
     Err(e) => Err(warp::reject::custom(e))
   }
 }
 
-pub async fn update_question(
+pub async fn update_question<S: Store>(
   id: i32,
-  store: Store,
-  question: Question,
-) -> Result<impl warp::Reply, warp::Rejection> {    
-  match store.update_question(question, id).await {
+  session: Session,
+  store: S,
+  moderator: SharedModerator,
+  question: NewQuestion,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let owner = store.get_question_owner(id).await?;
+  authorize(&store, &session, owner).await?;
+  let (question, needs_review) = moderate(&moderator, question).await?;
+
+  match store.update_question(question, id, session.account_id, needs_review).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn patch_question<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+  moderator: SharedModerator,
+  patch: QuestionPatch,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  if let Some(QuestionId(body_id)) = patch.id {
+    if body_id != id {
+      return Err(warp::reject::custom(ApiError::IdMismatchError { path: id, body: body_id }));
+    }
+  }
+
+  let owner = store.get_question_owner(id).await?;
+  authorize(&store, &session, owner).await?;
+
+  let title = match &patch.title {
+    Some(title) => Some(moderator.censor(title).await?),
+    None => None,
+  };
+  let content = match &patch.content {
+    Some(content) => Some(moderator.censor(content).await?),
+    None => None,
+  };
+  let (tags, tags_need_review) = match patch.tags {
+    Some(tags) => {
+      let (tags, needs_review) = moderate_tags(&moderator, tags).await?;
+      (Some(tags), needs_review)
+    }
+    None => (None, false),
+  };
+  let needs_review = [&title, &content].iter().any(|part| part.as_ref().is_some_and(|part| part.needs_review)) || tags_need_review;
+  let patch = QuestionPatch {
+    title: title.map(|title| title.text),
+    content: content.map(|content| content.text),
+    tags,
+    ..patch
+  };
+
+  match store.patch_question(patch, id, session.account_id, needs_review).await {
     Ok(res) => Ok(warp::reply::json(&res)),
     Err(e) => Err(warp::reject::custom(e)),
   }
 }
 
-pub async fn delete_question(
+pub async fn delete_question<S: Store>(
   id: i32,
-  store: Store,
+  session: Session,
+  store: S,
+  on_answers: OnQuestionDelete,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-  match store.delete_question(id).await {
-    Ok(_) => Ok(warp::reply::with_status(format!("question {} deleted", id), StatusCode::OK)),
+  let owner = store.get_question_owner(id).await?;
+  authorize(&store, &session, owner).await?;
+
+  match store.delete_question(id, on_answers).await {
+    Ok(answers) if answers.is_empty() => Ok(warp::reply::with_status(format!("question {} deleted", id), StatusCode::OK)),
+    Ok(answers) => Ok(warp::reply::with_status(
+      format!("question {} deleted with {} answer(s)", id, answers.len()),
+      StatusCode::OK,
+    )),
     Err(e) => Err(warp::reject::custom(e)),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::config::{ModerationBackend, OnQuestionDelete};
+  use crate::testing::{authorized, config, post_question, TestApi};
+  use serde_json::{json, Value};
+  use warp::http::StatusCode;
+
+  #[tokio::test]
+  async fn questions_can_be_created_read_updated_and_deleted() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+
+    let (status, question) = api.send(post_question(&token, "first")).await;
+    assert_eq!(status, StatusCode::OK);
+    let path = format!("/questions/{}", question["id"]);
+
+    let (status, question) = api.send(warp::test::request().path(&path)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(question["title"], "first");
+
+    let update = json!({ "title": "second", "content": "new content", "tags": null });
+    let (status, question) = api.send(authorized("PUT", &path, &token).json(&update)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(question["title"], "second");
+    assert_eq!(question["tags"], Value::Null);
+
+    let (status, question) = api.send(authorized("PATCH", &path, &token).json(&json!({ "content": "patched" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((question["title"].as_str(), question["content"].as_str()), (Some("second"), Some("patched")));
+
+    let (status, questions) = api.send(warp::test::request().path("/questions")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(questions.as_array().map(Vec::len), Some(1));
+
+    assert_eq!(api.send(authorized("DELETE", &path, &token)).await.0, StatusCode::OK);
+
+    let (status, problem) = api.send(warp::test::request().path(&path)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "question_not_found");
+  }
+
+  #[tokio::test]
+  async fn questions_are_only_changed_by_their_author() {
+    let api = TestApi::new();
+    let author = api.sign_in("author@example.com").await;
+    let other = api.sign_in("other@example.com").await;
+
+    let unauthenticated = warp::test::request()
+      .method("POST")
+      .path("/questions")
+      .json(&json!({ "title": "t", "content": "c" }));
+    assert_eq!(api.send(unauthenticated).await.0, StatusCode::UNAUTHORIZED);
+
+    let id = api.ask(&author, "mine").await;
+    let (status, problem) = api.send(authorized("DELETE", &format!("/questions/{}", id), &other)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(problem["code"], "forbidden");
+  }
+
+  #[tokio::test]
+  async fn questions_with_answers_are_kept_when_deletes_block() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+    let id = api.ask(&token, "answered").await;
+    let answer = api.answer(&token, id).await;
+
+    let (status, problem) = api.send(authorized("DELETE", &format!("/questions/{}", id), &token)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(problem["code"], "question_has_answers");
+    assert_eq!(problem["answers"], json!([answer]));
+
+    let (status, answers) = api.send(warp::test::request().path(&format!("/questions/{}/answers", id))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(answers.as_array().map(Vec::len), Some(1));
+  }
+
+  #[tokio::test]
+  async fn questions_take_their_answers_along_when_deletes_cascade() {
+    let mut config = config();
+    config.storage.on_question_delete = OnQuestionDelete::Cascade;
+    let api = TestApi::with_config(&config);
+    let token = api.sign_in("author@example.com").await;
+    let id = api.ask(&token, "answered").await;
+    let answer = api.answer(&token, id).await;
+
+    assert_eq!(api.send(authorized("DELETE", &format!("/questions/{}", id), &token)).await.0, StatusCode::OK);
+    assert_eq!(api.send(warp::test::request().path(&format!("/questions/{}", id))).await.0, StatusCode::NOT_FOUND);
+
+    let admin = api.sign_in_admin("admin@example.com").await;
+    let (_, trash) = api.send(authorized("GET", "/trash", &admin)).await;
+    assert_eq!(trash["answers"][0]["id"], answer);
+  }
+
+  #[tokio::test]
+  async fn tags_are_censored_like_titles_and_content() {
+    let mut config = config();
+    config.moderation.backend = ModerationBackend::WordList;
+    config.moderation.words = vec!["darn".to_string()];
+    let api = TestApi::with_config(&config);
+    let token = api.sign_in("author@example.com").await;
+
+    let new_question = json!({ "title": "darn", "content": "c", "tags": ["rust", "Darn", "darn-it"] });
+    let (status, question) = api.send(authorized("POST", "/questions", &token).json(&new_question)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(question["title"], "****");
+    assert_eq!(question["tags"], json!(["rust", "****", "****-it"]));
+
+    let patch = authorized("PATCH", &format!("/questions/{}", question["id"]), &token).json(&json!({ "tags": ["darn"] }));
+    assert_eq!(api.send(patch).await.1["tags"], json!(["****"]));
+  }
+
+  #[tokio::test]
+  async fn unchanged_questions_are_not_sent_again() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+    let path = format!("/questions/{}", api.ask(&token, "cached").await);
+
+    let res = api.reply(warp::test::request().path(&path)).await;
+    let etag = res.headers()["etag"].to_str().unwrap().to_string();
+    let last_modified = res.headers()["last-modified"].to_str().unwrap().to_string();
+
+    let res = api.reply(warp::test::request().path(&path).header("if-none-match", &etag)).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert!(res.body().is_empty());
+    let res = api.reply(warp::test::request().path(&path).header("if-modified-since", &last_modified)).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    api.send(authorized("PATCH", &path, &token).json(&json!({ "content": "changed" }))).await;
+    let res = api.reply(warp::test::request().path(&path).header("if-none-match", &etag)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_ne!(res.headers()["etag"].to_str().unwrap(), etag);
+  }
+}
@@ -0,0 +1,40 @@
+use crate::routes::authentication::authorize;
+use crate::store::Store;
+use crate::types::account::Session;
+
+/// items stored unmoderated while the moderation service was down, admins only
+pub async fn get_reviews<S: Store>(
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  authorize(&store, &session, None).await?;
+
+  match store.get_reviews().await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::store::Store;
+  use crate::testing::{authorized, TestApi};
+  use crate::types::account::AccountId;
+  use crate::types::question::NewQuestion;
+  use warp::http::StatusCode;
+
+  #[tokio::test]
+  async fn reviews_are_listed_for_admins_only() {
+    let api = TestApi::new();
+    let question = NewQuestion { title: "t".to_string(), content: "c".to_string(), tags: None };
+    let question = api.store.add_question(question, AccountId(1), true).await.unwrap();
+
+    let user = api.sign_in("user@example.com").await;
+    assert_eq!(api.send(authorized("GET", "/reviews", &user)).await.0, StatusCode::FORBIDDEN);
+
+    let admin = api.sign_in_admin("admin@example.com").await;
+    let (status, reviews) = api.send(authorized("GET", "/reviews", &admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(reviews[0]["question_id"], question.id.0);
+  }
+}
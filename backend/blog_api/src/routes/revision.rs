@@ -0,0 +1,99 @@
+use error_handler::ApiError;
+use std::collections::HashMap;
+
+use crate::routes::authentication::authorize;
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::revision::{extract_diff_range, RevisionDiff};
+
+pub async fn get_revisions<S: Store>(
+  id: i32,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  match store.get_revisions(id).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+/// line diff between the revisions `from` and `to` of a question,
+/// by default between the latest revision and the one before it
+pub async fn diff_revisions<S: Store>(
+  id: i32,
+  params: HashMap<String, String>,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let range = extract_diff_range(&params)?;
+  let revisions = store.get_revisions(id).await?;
+
+  let revision = |number: i32| {
+    revisions
+      .iter()
+      .find(|revision| revision.revision == number)
+      .ok_or(ApiError::RevisionNotFoundError { question: id, revision: number })
+  };
+  let to = match range.to {
+    Some(to) => revision(to)?,
+    None => revisions.last().ok_or(ApiError::RevisionNotFoundError { question: id, revision: 1 })?,
+  };
+  let from = revision(range.from.unwrap_or((to.revision - 1).max(1)))?;
+
+  Ok(warp::reply::json(&RevisionDiff::between(from, to)))
+}
+
+/// saves an old revision again as the newest one, for the author or an admin
+pub async fn revert_revision<S: Store>(
+  id: i32,
+  revision: i32,
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let owner = store.get_question_owner(id).await?;
+  authorize(&store, &session, owner).await?;
+  let revision = store.get_revision(id, revision).await?;
+
+  // the revision was moderated, or queued for review, when it was first saved
+  match store.update_question(revision.into(), id, session.account_id, false).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::{authorized, TestApi};
+  use serde_json::json;
+  use warp::http::StatusCode;
+
+  #[tokio::test]
+  async fn reverts_save_an_old_revision_as_the_newest() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+    let id = api.ask(&token, "first").await;
+
+    let update = json!({ "title": "second", "content": "changed", "tags": null });
+    assert_eq!(api.send(authorized("PUT", &format!("/questions/{}", id), &token).json(&update)).await.0, StatusCode::OK);
+
+    let (status, question) = api.send(authorized("POST", &format!("/questions/{}/revisions/1/revert", id), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((question["title"].as_str(), question["content"].as_str()), (Some("first"), Some("content")));
+    assert_eq!(question["tags"], json!(["rust"]));
+
+    let (_, revisions) = api.send(warp::test::request().path(&format!("/questions/{}/revisions", id))).await;
+    let titles: Vec<(i64, &str)> = revisions
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|revision| (revision["revision"].as_i64().unwrap(), revision["title"].as_str().unwrap()))
+      .collect();
+    assert_eq!(titles, vec![(1, "first"), (2, "second"), (3, "first")]);
+
+    let (status, diff) = api.send(warp::test::request().path(&format!("/questions/{}/revisions/diff?from=2&to=3", id))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(diff["title"], json!([{ "op": "delete", "text": "second" }, { "op": "insert", "text": "first" }]));
+
+    let (status, problem) = api.send(authorized("POST", &format!("/questions/{}/revisions/9/revert", id), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "revision_not_found");
+  }
+}
@@ -0,0 +1,15 @@
+use crate::store::Store;
+use crate::types::tag::extract_tag_sort;
+use std::collections::HashMap;
+
+pub async fn get_tags<S: Store>(
+  params: HashMap<String, String>,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let sort = extract_tag_sort(&params)?;
+
+  match store.get_tags(sort).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
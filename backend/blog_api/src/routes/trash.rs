@@ -0,0 +1,115 @@
+use crate::routes::authentication::authorize;
+use crate::store::Store;
+use crate::types::account::Session;
+
+/// deleted questions and answers, admins only
+pub async fn get_trash<S: Store>(
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  authorize(&store, &session, None).await?;
+
+  match store.get_trash().await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+/// takes a question and the answers deleted with it out of the trash, admins only
+pub async fn restore_question<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  authorize(&store, &session, None).await?;
+
+  match store.restore_question(id).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+/// takes an answer out of the trash, admins only
+pub async fn restore_answer<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  authorize(&store, &session, None).await?;
+
+  match store.restore_answer(id).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::OnQuestionDelete;
+  use crate::testing::{authorized, config, TestApi};
+  use serde_json::Value;
+  use warp::http::StatusCode;
+
+  fn ids(items: &Value) -> Vec<i64> {
+    items.as_array().unwrap().iter().map(|item| item["id"].as_i64().unwrap()).collect()
+  }
+
+  #[tokio::test]
+  async fn deleted_questions_wait_in_the_trash_until_restored() {
+    let mut config = config();
+    config.storage.on_question_delete = OnQuestionDelete::Cascade;
+    let api = TestApi::with_config(&config);
+    let author = api.sign_in("author@example.com").await;
+    let admin = api.sign_in_admin("admin@example.com").await;
+    let question = api.ask(&author, "deleted").await;
+    let answer = api.answer(&author, question).await;
+
+    assert_eq!(api.send(authorized("GET", "/trash", &author)).await.0, StatusCode::FORBIDDEN);
+
+    assert_eq!(api.send(authorized("DELETE", &format!("/questions/{}", question), &author)).await.0, StatusCode::OK);
+    let (status, trash) = api.send(authorized("GET", "/trash", &admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((ids(&trash["questions"]), ids(&trash["answers"])), (vec![question], vec![answer]));
+    assert!(trash["questions"][0]["deleted_at"].is_string());
+
+    let (status, restored) = api.send(authorized("POST", &format!("/questions/{}/restore", question), &admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(restored["deleted_at"], Value::Null);
+
+    let (_, answers) = api.send(warp::test::request().path(&format!("/questions/{}/answers", question))).await;
+    assert_eq!(ids(&answers), vec![answer]);
+    let (_, trash) = api.send(authorized("GET", "/trash", &admin)).await;
+    assert_eq!((ids(&trash["questions"]), ids(&trash["answers"])), (vec![], vec![]));
+
+    let (status, problem) = api.send(authorized("POST", &format!("/questions/{}/restore", question), &admin)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "question_not_found");
+  }
+
+  #[tokio::test]
+  async fn answers_are_only_restored_under_a_live_question() {
+    let api = TestApi::new();
+    let author = api.sign_in("author@example.com").await;
+    let admin = api.sign_in_admin("admin@example.com").await;
+    let question = api.ask(&author, "asked").await;
+    let answer = api.answer(&author, question).await;
+
+    assert_eq!(api.send(authorized("DELETE", &format!("/answers/{}", answer), &author)).await.0, StatusCode::OK);
+    assert_eq!(api.send(authorized("DELETE", &format!("/questions/{}", question), &author)).await.0, StatusCode::OK);
+
+    let restore_answer = || authorized("POST", &format!("/answers/{}/restore", answer), &admin);
+    let (status, problem) = api.send(restore_answer()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "question_not_found");
+
+    // the answer was deleted on its own, restoring the question leaves it in the trash
+    assert_eq!(api.send(authorized("POST", &format!("/questions/{}/restore", question), &admin)).await.0, StatusCode::OK);
+    let (_, answers) = api.send(warp::test::request().path(&format!("/questions/{}/answers", question))).await;
+    assert_eq!(ids(&answers), Vec::<i64>::new());
+
+    let (status, restored) = api.send(restore_answer()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(restored["id"], answer);
+    assert_eq!(api.send(restore_answer()).await.1["code"], "answer_not_found");
+  }
+}
@@ -0,0 +1,130 @@
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+use crate::types::vote::{NewVote, VoteItem};
+
+/// votes a question up or down, voting again replaces the earlier vote
+pub async fn vote_question<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+  vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  match store.vote(VoteItem::Question(QuestionId(id)), session.account_id, Some(vote.direction)).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn retract_question_vote<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  match store.vote(VoteItem::Question(QuestionId(id)), session.account_id, None).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+/// votes an answer up or down, voting again replaces the earlier vote
+pub async fn vote_answer<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+  vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  match store.vote(VoteItem::Answer(AnswerId(id)), session.account_id, Some(vote.direction)).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+pub async fn retract_answer_vote<S: Store>(
+  id: i32,
+  session: Session,
+  store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  match store.vote(VoteItem::Answer(AnswerId(id)), session.account_id, None).await {
+    Ok(res) => Ok(warp::reply::json(&res)),
+    Err(e) => Err(warp::reject::custom(e)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::{authorized, TestApi};
+  use serde_json::{json, Value};
+  use warp::http::StatusCode;
+
+  async fn vote(api: &TestApi, path: &str, token: &str, direction: &str) -> Value {
+    let (status, votes) = api.send(authorized("PUT", path, token).json(&json!({ "direction": direction }))).await;
+    assert_eq!(status, StatusCode::OK);
+    votes
+  }
+
+  fn ids(items: &Value) -> Vec<i64> {
+    items.as_array().unwrap().iter().map(|item| item["id"].as_i64().unwrap()).collect()
+  }
+
+  #[tokio::test]
+  async fn votes_can_be_changed_repeated_and_retracted() {
+    let api = TestApi::new();
+    let author = api.sign_in("author@example.com").await;
+    let voter = api.sign_in("voter@example.com").await;
+    let path = format!("/questions/{}/vote", api.ask(&author, "voted").await);
+
+    assert_eq!(vote(&api, &path, &voter, "up").await, json!({ "score": 1, "vote": "up" }));
+    assert_eq!(vote(&api, &path, &voter, "up").await, json!({ "score": 1, "vote": "up" }), "one vote per account");
+    assert_eq!(vote(&api, &path, &voter, "down").await, json!({ "score": -1, "vote": "down" }));
+    assert_eq!(vote(&api, &path, &author, "down").await, json!({ "score": -2, "vote": "down" }));
+
+    let (status, votes) = api.send(authorized("DELETE", &path, &voter)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(votes, json!({ "score": -1, "vote": null }));
+    assert_eq!(api.send(authorized("DELETE", &path, &voter)).await.1["score"], -1, "retracting twice changes nothing");
+
+    let (status, problem) = api.send(authorized("PUT", "/questions/999/vote", &voter).json(&json!({ "direction": "up" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["code"], "question_not_found");
+    let unauthenticated = warp::test::request().method("PUT").path(&path).json(&json!({ "direction": "up" }));
+    assert_eq!(api.send(unauthenticated).await.0, StatusCode::UNAUTHORIZED);
+  }
+
+  #[tokio::test]
+  async fn questions_can_be_sorted_by_score() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+    let (low, high, middle) = (api.ask(&token, "low").await, api.ask(&token, "high").await, api.ask(&token, "middle").await);
+
+    vote(&api, &format!("/questions/{}/vote", low), &token, "down").await;
+    vote(&api, &format!("/questions/{}/vote", high), &token, "up").await;
+
+    let (_, questions) = api.send(warp::test::request().path("/questions?sort=score")).await;
+    assert_eq!(ids(&questions), vec![high, middle, low]);
+    let (_, page) = api.send(warp::test::request().path("/questions?sort=score&limit=2")).await;
+    assert_eq!(ids(&page["items"]), vec![high, middle]);
+    let (_, questions) = api.send(warp::test::request().path("/questions")).await;
+    assert_eq!(ids(&questions), vec![low, high, middle]);
+  }
+
+  #[tokio::test]
+  async fn answers_are_ranked_by_score() {
+    let api = TestApi::new();
+    let token = api.sign_in("author@example.com").await;
+    let voter = api.sign_in("voter@example.com").await;
+    let question = api.ask(&token, "answered").await;
+    let (first, second, third) = (api.answer(&token, question).await, api.answer(&token, question).await, api.answer(&token, question).await);
+
+    vote(&api, &format!("/answers/{}/vote", third), &voter, "up").await;
+    vote(&api, &format!("/answers/{}/vote", first), &voter, "down").await;
+    let (_, answers) = api.send(warp::test::request().path(&format!("/questions/{}/answers", question))).await;
+    assert_eq!(ids(&answers), vec![third, second, first]);
+
+    let (_, votes) = api.send(authorized("DELETE", &format!("/answers/{}/vote", first), &voter)).await;
+    assert_eq!(votes["score"], 0);
+    let (_, answers) = api.send(warp::test::request().path(&format!("/questions/{}/answers", question))).await;
+    assert_eq!(ids(&answers), vec![third, first, second], "equal scores keep the oldest first");
+  }
+}
@@ -0,0 +1,720 @@
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use error_handler::ApiError;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::OnQuestionDelete;
+use crate::store::Store;
+use crate::types::account::{Account, AccountId, Role};
+use crate::types::answer::{Answer, AnswerId, AnswerUpdate, NewAnswer};
+use crate::types::filter::{QuestionFilter, QuestionSort};
+use crate::types::pagination::{Cursor, CursorKey, CursorPagination, Page};
+use crate::types::question::{NewQuestion, Question, QuestionId, QuestionPatch};
+use crate::types::review::{Review, ReviewItem};
+use crate::types::revision::Revision;
+use crate::types::tag::{Tag, TagSort};
+use crate::types::trash::{Purged, Trash};
+use crate::types::vote::{Direction, VoteItem, Votes};
+
+/// entry of a `questions.json` seed file
+#[derive(Deserialize)]
+struct SeedQuestion {
+  /// like `QI0001`, the digits become the question id
+  id: String,
+  title: String,
+  content: String,
+  tags: Option<Vec<String>>,
+}
+
+#[derive(Debug)]
+struct AccountRecord {
+  account: Account,
+  role: Role,
+}
+
+#[derive(Debug, Default)]
+struct Data {
+  questions: BTreeMap<i32, Question>,
+  answers: BTreeMap<i32, Answer>,
+  accounts: BTreeMap<i32, AccountRecord>,
+  reviews: BTreeMap<i32, Review>,
+  /// by question id, oldest first
+  revisions: BTreeMap<i32, Vec<Revision>>,
+  /// by `(question id, account id)`
+  question_votes: HashMap<(i32, i32), Direction>,
+  /// by `(answer id, account id)`
+  answer_votes: HashMap<(i32, i32), Direction>,
+  last_question_id: i32,
+  last_answer_id: i32,
+  last_account_id: i32,
+  last_review_id: i32,
+}
+
+impl Data {
+  /// the question exists and is not in the trash
+  fn has_question(&self, id: i32) -> bool {
+    self.questions.get(&id).is_some_and(|question| question.deleted_at.is_none())
+  }
+
+  /// saves `question` as its next revision
+  fn add_revision(&mut self, question: &Question, account_id: Option<AccountId>) {
+    let revisions = self.revisions.entry(question.id.0).or_default();
+    revisions.push(Revision::of(question, revisions.len() as i32 + 1, account_id));
+  }
+
+  /// flags an item stored while moderation was unavailable
+  fn queue_review(&mut self, item: ReviewItem) {
+    self.last_review_id += 1;
+
+    let (question_id, answer_id) = match item {
+      ReviewItem::Question(id) => (Some(id), None),
+      ReviewItem::Answer(id) => (None, Some(id)),
+    };
+    let review = Review {
+      id: self.last_review_id,
+      question_id,
+      answer_id,
+      created_at: Utc::now().naive_utc(),
+    };
+    self.reviews.insert(review.id, review);
+  }
+}
+
+/// keeps everything in process memory, nothing survives a restart.
+/// Full-text search is a plain case-insensitive match on every search term.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+  data: Arc<RwLock<Data>>,
+}
+
+impl MemoryStore {
+  pub fn new() -> Self {
+    MemoryStore::default()
+  }
+
+  /// loads questions from a file in the `questions.json` format,
+  /// a map from ids like `QI0001` to questions
+  pub fn from_file(path: &Path) -> Result<Self, String> {
+    let raw = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let seed: HashMap<String, SeedQuestion> =
+      serde_json::from_str(&raw).map_err(|e| format!("cannot parse {}: {}", path.display(), e))?;
+
+    let mut seed: Vec<SeedQuestion> = seed.into_values().collect();
+    seed.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let store = MemoryStore::new();
+    {
+      let mut data = store.data.write();
+      let now = Utc::now().naive_utc();
+
+      for question in seed {
+        let id = match question.id.trim_start_matches(|c: char| !c.is_ascii_digit()).parse() {
+          Ok(id) if !data.questions.contains_key(&id) => id,
+          _ => data.last_question_id + 1,
+        };
+
+        data.last_question_id = data.last_question_id.max(id);
+        let question = Question {
+          id: QuestionId(id),
+          title: question.title,
+          content: question.content,
+          tags: question.tags,
+          created_at: now,
+          updated_at: now,
+          account_id: None,
+          score: 0,
+          deleted_at: None,
+        };
+        data.add_revision(&question, None);
+        data.questions.insert(id, question);
+      }
+    }
+
+    Ok(store)
+  }
+
+  /// makes the account behind `email` an admin, which only the database can do otherwise
+  #[cfg(test)]
+  pub fn promote(&self, email: &str) {
+    let mut data = self.data.write();
+    if let Some(record) = data.accounts.values_mut().find(|record| record.account.email == email) {
+      record.role = Role::Admin;
+    }
+  }
+}
+
+/// relevance of a question for the search terms, `None` when a term is missing.
+/// Title hits weigh more than content hits, like the postgres ranking.
+fn relevance(question: &Question, terms: &[String]) -> Option<usize> {
+  let title = question.title.to_lowercase();
+  let content = question.content.to_lowercase();
+
+  terms.iter().try_fold(0, |rank, term| {
+    match (title.contains(term.as_str()), content.contains(term.as_str())) {
+      (false, false) => None,
+      (in_title, in_content) => Some(rank + 2 * in_title as usize + in_content as usize),
+    }
+  })
+}
+
+fn has_tags(question: &Question, filter: &QuestionFilter) -> bool {
+  let tags = question.tags.as_deref().unwrap_or_default();
+
+  let any = filter.tags_any.as_ref().is_none_or(|any| any.iter().any(|tag| tags.contains(tag)));
+  let all = filter.tags_all.as_ref().is_none_or(|all| all.iter().all(|tag| tags.contains(tag)));
+
+  any && all
+}
+
+fn key(question: &Question) -> CursorKey {
+  CursorKey {
+    created_at: question.created_at,
+    id: question.id.0,
+  }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+  async fn ping(&self) -> Result<(), ApiError> {
+    Ok(())
+  }
+
+  /// there is no schema to migrate
+  async fn pending_migrations(&self) -> Result<Vec<i64>, ApiError> {
+    Ok(Vec::new())
+  }
+
+  async fn get_questions(&self, limit: Option<i32>, offset: i32, filter: QuestionFilter, sort: QuestionSort) -> Result<Vec<Question>, ApiError> {
+    let data = self.data.read();
+
+    let terms: Vec<String> = filter
+      .search
+      .as_deref()
+      .map(|search| search.split_whitespace().map(str::to_lowercase).collect())
+      .unwrap_or_default();
+
+    let mut ranked: Vec<(usize, &Question)> = data
+      .questions
+      .values()
+      .filter(|question| question.deleted_at.is_none() && has_tags(question, &filter))
+      .filter_map(|question| relevance(question, &terms).map(|rank| (rank, question)))
+      .collect();
+    let score = |question: &Question| if sort == QuestionSort::Score { question.score } else { 0 };
+    ranked.sort_by(|(rank_a, a), (rank_b, b)| {
+      score(b).cmp(&score(a)).then(rank_b.cmp(rank_a)).then(a.id.0.cmp(&b.id.0))
+    });
+
+    Ok(ranked
+      .into_iter()
+      .skip(offset.max(0) as usize)
+      .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+      .map(|(_, question)| question.clone())
+      .collect())
+  }
+
+  async fn get_questions_page(&self, pagination: CursorPagination, filter: QuestionFilter, sort: QuestionSort) -> Result<Page<Question>, ApiError> {
+    let foreign_cursor = || ApiError::InvalidParamError("cursor does not belong to this query".to_string());
+    let limit = pagination.limit;
+
+    // relevance and score rankings have no stable keyset, so they are paged by offset
+    if filter.search.is_some() || sort == QuestionSort::Score {
+      let offset = match pagination.cursor {
+        None => 0,
+        Some(Cursor::Offset(offset)) => offset,
+        Some(_) => return Err(foreign_cursor()),
+      };
+
+      let questions = self.get_questions(Some(limit + 1), offset, filter, sort).await?;
+      return Ok(Page::from_offset(questions, limit, offset));
+    }
+
+    let data = self.data.read();
+    let mut questions: Vec<&Question> = data
+      .questions
+      .values()
+      .filter(|question| question.deleted_at.is_none() && has_tags(question, &filter))
+      .collect();
+    questions.sort_by_key(|question| (question.created_at, question.id.0));
+
+    let rows: Vec<&Question> = match pagination.cursor {
+      None => questions.into_iter().take(limit as usize + 1).collect(),
+      Some(Cursor::After(after)) => questions
+        .into_iter()
+        .filter(|question| key(question) > after)
+        .take(limit as usize + 1)
+        .collect(),
+      Some(Cursor::Before(before)) => questions
+        .into_iter()
+        .rev()
+        .filter(|question| key(question) < before)
+        .take(limit as usize + 1)
+        .collect(),
+      Some(Cursor::Offset(_)) => return Err(foreign_cursor()),
+    };
+
+    let rows = rows.into_iter().map(|question| (key(question), question.clone())).collect();
+    Ok(Page::from_keyset(rows, limit, pagination.cursor))
+  }
+
+  async fn get_tags(&self, sort: TagSort) -> Result<Vec<Tag>, ApiError> {
+    let data = self.data.read();
+    let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+
+    for question in data.questions.values().filter(|question| question.deleted_at.is_none()) {
+      let mut tags: Vec<&str> = question.tags.iter().flatten().map(String::as_str).collect();
+      tags.sort_unstable();
+      tags.dedup();
+
+      for tag in tags {
+        *counts.entry(tag).or_default() += 1;
+      }
+    }
+
+    let mut tags: Vec<Tag> = counts
+      .into_iter()
+      .map(|(name, questions)| Tag { name: name.to_string(), questions })
+      .collect();
+
+    if sort == TagSort::Popularity {
+      tags.sort_by(|a, b| b.questions.cmp(&a.questions).then_with(|| a.name.cmp(&b.name)));
+    }
+
+    Ok(tags)
+  }
+
+  async fn get_question(&self, id: i32) -> Result<Question, ApiError> {
+    self
+      .data
+      .read()
+      .questions
+      .get(&id)
+      .filter(|question| question.deleted_at.is_none())
+      .cloned()
+      .ok_or(ApiError::QuestionNotFoundError(id))
+  }
+
+  async fn add_question(&self, new_question: NewQuestion, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError> {
+    let mut data = self.data.write();
+    let now = Utc::now().naive_utc();
+
+    data.last_question_id += 1;
+    let question = Question {
+      id: QuestionId(data.last_question_id),
+      title: new_question.title,
+      content: new_question.content,
+      tags: new_question.tags,
+      created_at: now,
+      updated_at: now,
+      account_id: Some(account_id),
+      score: 0,
+      deleted_at: None,
+    };
+
+    data.questions.insert(question.id.0, question.clone());
+    data.add_revision(&question, Some(account_id));
+    if needs_review {
+      data.queue_review(ReviewItem::Question(question.id.clone()));
+    }
+    Ok(question)
+  }
+
+  async fn update_question(&self, question: NewQuestion, id: i32, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError> {
+    let mut data = self.data.write();
+    let stored = data
+      .questions
+      .get_mut(&id)
+      .filter(|question| question.deleted_at.is_none())
+      .ok_or(ApiError::QuestionNotFoundError(id))?;
+
+    stored.title = question.title;
+    stored.content = question.content;
+    stored.tags = question.tags;
+    stored.updated_at = Utc::now().naive_utc();
+
+    let question = stored.clone();
+    data.add_revision(&question, Some(account_id));
+    if needs_review {
+      data.queue_review(ReviewItem::Question(question.id.clone()));
+    }
+    Ok(question)
+  }
+
+  async fn patch_question(&self, patch: QuestionPatch, id: i32, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError> {
+    let mut data = self.data.write();
+    let stored = data
+      .questions
+      .get_mut(&id)
+      .filter(|question| question.deleted_at.is_none())
+      .ok_or(ApiError::QuestionNotFoundError(id))?;
+
+    if let Some(title) = patch.title {
+      stored.title = title;
+    }
+    if let Some(content) = patch.content {
+      stored.content = content;
+    }
+    if let Some(tags) = patch.tags {
+      stored.tags = tags;
+    }
+    stored.updated_at = Utc::now().naive_utc();
+
+    let question = stored.clone();
+    data.add_revision(&question, Some(account_id));
+    if needs_review {
+      data.queue_review(ReviewItem::Question(question.id.clone()));
+    }
+    Ok(question)
+  }
+
+  async fn delete_question(&self, id: i32, on_answers: OnQuestionDelete) -> Result<Vec<AnswerId>, ApiError> {
+    // one write lock, so nothing can answer the question in between
+    let mut data = self.data.write();
+
+    if !data.has_question(id) {
+      return Err(ApiError::QuestionNotFoundError(id));
+    }
+
+    let answers: Vec<AnswerId> = data.answers.values()
+      .filter(|answer| answer.question_id.0 == id && answer.deleted_at.is_none())
+      .map(|answer| answer.id.clone())
+      .collect();
+    if !answers.is_empty() && on_answers == OnQuestionDelete::Block {
+      return Err(ApiError::QuestionHasAnswersError { id, answers: answers.iter().map(|answer| answer.0).collect() });
+    }
+
+    // the shared timestamp tells restore which answers went with the question
+    let now = Utc::now().naive_utc();
+    for answer in &answers {
+      if let Some(answer) = data.answers.get_mut(&answer.0) {
+        answer.deleted_at = Some(now);
+      }
+    }
+    if let Some(question) = data.questions.get_mut(&id) {
+      question.deleted_at = Some(now);
+    }
+    Ok(answers)
+  }
+
+  async fn get_question_owner(&self, id: i32) -> Result<Option<AccountId>, ApiError> {
+    self.get_question(id).await.map(|question| question.account_id)
+  }
+
+  async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, ApiError> {
+    let data = self.data.read();
+
+    if !data.has_question(question_id) {
+      return Err(ApiError::QuestionNotFoundError(question_id));
+    }
+    Ok(data.revisions.get(&question_id).cloned().unwrap_or_default())
+  }
+
+  async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, ApiError> {
+    self
+      .get_revisions(question_id)
+      .await?
+      .into_iter()
+      .find(|stored| stored.revision == revision)
+      .ok_or(ApiError::RevisionNotFoundError { question: question_id, revision })
+  }
+
+  async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, ApiError> {
+    self.get_question(question_id).await?;
+
+    let data = self.data.read();
+    let mut answers: Vec<&Answer> = data
+      .answers
+      .values()
+      .filter(|answer| answer.question_id.0 == question_id && answer.deleted_at.is_none())
+      .collect();
+    // ids grow with time, the sort is stable
+    answers.sort_by_key(|answer| std::cmp::Reverse(answer.score));
+
+    Ok(answers
+      .into_iter()
+      .skip(offset.max(0) as usize)
+      .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+      .cloned()
+      .collect())
+  }
+
+  async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId, needs_review: bool) -> Result<Answer, ApiError> {
+    let mut data = self.data.write();
+    let question_id = new_answer.question_id.0;
+
+    if !data.has_question(question_id) {
+      return Err(ApiError::QuestionNotFoundError(question_id));
+    }
+
+    data.last_answer_id += 1;
+    let answer = Answer {
+      id: AnswerId(data.last_answer_id),
+      content: new_answer.content,
+      question_id: new_answer.question_id,
+      account_id: Some(account_id),
+      score: 0,
+      deleted_at: None,
+    };
+
+    data.answers.insert(answer.id.0, answer.clone());
+    if needs_review {
+      data.queue_review(ReviewItem::Answer(answer.id.clone()));
+    }
+    Ok(answer)
+  }
+
+  async fn update_answer(&self, update: AnswerUpdate, id: i32, needs_review: bool) -> Result<Answer, ApiError> {
+    let mut data = self.data.write();
+    let stored = data
+      .answers
+      .get_mut(&id)
+      .filter(|answer| answer.deleted_at.is_none())
+      .ok_or(ApiError::AnswerNotFoundError(id))?;
+
+    stored.content = update.content;
+
+    let answer = stored.clone();
+    if needs_review {
+      data.queue_review(ReviewItem::Answer(answer.id.clone()));
+    }
+    Ok(answer)
+  }
+
+  async fn delete_answer(&self, id: i32) -> Result<bool, ApiError> {
+    let mut data = self.data.write();
+
+    match data.answers.get_mut(&id).filter(|answer| answer.deleted_at.is_none()) {
+      Some(answer) => {
+        answer.deleted_at = Some(Utc::now().naive_utc());
+        Ok(true)
+      }
+      None => Err(ApiError::AnswerNotFoundError(id)),
+    }
+  }
+
+  async fn get_answer_owner(&self, id: i32) -> Result<Option<AccountId>, ApiError> {
+    self
+      .data
+      .read()
+      .answers
+      .get(&id)
+      .filter(|answer| answer.deleted_at.is_none())
+      .map(|answer| answer.account_id)
+      .ok_or(ApiError::AnswerNotFoundError(id))
+  }
+
+  async fn add_account(&self, account: Account) -> Result<Account, ApiError> {
+    let mut data = self.data.write();
+
+    if data.accounts.values().any(|record| record.account.email == account.email) {
+      return Err(ApiError::DuplicateAccountError);
+    }
+
+    data.last_account_id += 1;
+    let id = data.last_account_id;
+    let account = Account {
+      id: Some(AccountId(id)),
+      ..account
+    };
+
+    data.accounts.insert(id, AccountRecord {
+      account: account.clone(),
+      role: Role::User,
+    });
+    Ok(account)
+  }
+
+  async fn get_account(&self, email: &str) -> Result<Option<Account>, ApiError> {
+    Ok(self
+      .data
+      .read()
+      .accounts
+      .values()
+      .find(|record| record.account.email == email)
+      .map(|record| record.account.clone()))
+  }
+
+  async fn get_role(&self, account_id: AccountId) -> Result<Role, ApiError> {
+    self
+      .data
+      .read()
+      .accounts
+      .get(&account_id.0)
+      .map(|record| record.role)
+      // the token outlived its account
+      .ok_or(ApiError::UnauthorizedError)
+  }
+
+  async fn get_reviews(&self) -> Result<Vec<Review>, ApiError> {
+    Ok(self.data.read().reviews.values().cloned().collect())
+  }
+
+  async fn vote(&self, item: VoteItem, account_id: AccountId, direction: Option<Direction>) -> Result<Votes, ApiError> {
+    let mut data = self.data.write();
+    let data = &mut *data;
+
+    let (score, votes, id) = match item {
+      VoteItem::Question(QuestionId(id)) => match data.questions.get_mut(&id).filter(|question| question.deleted_at.is_none()) {
+        Some(question) => (&mut question.score, &mut data.question_votes, id),
+        None => return Err(ApiError::QuestionNotFoundError(id)),
+      },
+      VoteItem::Answer(AnswerId(id)) => match data.answers.get_mut(&id).filter(|answer| answer.deleted_at.is_none()) {
+        Some(answer) => (&mut answer.score, &mut data.answer_votes, id),
+        None => return Err(ApiError::AnswerNotFoundError(id)),
+      },
+    };
+
+    let previous = match direction {
+      Some(direction) => votes.insert((id, account_id.0), direction),
+      None => votes.remove(&(id, account_id.0)),
+    };
+    *score += direction.map_or(0, Direction::value) - previous.map_or(0, Direction::value);
+
+    Ok(Votes { score: *score, vote: direction })
+  }
+
+  async fn get_trash(&self) -> Result<Trash, ApiError> {
+    let data = self.data.read();
+
+    let mut questions: Vec<Question> = data.questions.values().filter(|question| question.deleted_at.is_some()).cloned().collect();
+    questions.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.0.cmp(&b.id.0)));
+    let mut answers: Vec<Answer> = data.answers.values().filter(|answer| answer.deleted_at.is_some()).cloned().collect();
+    answers.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.0.cmp(&b.id.0)));
+
+    Ok(Trash { questions, answers })
+  }
+
+  async fn restore_question(&self, id: i32) -> Result<Question, ApiError> {
+    let mut data = self.data.write();
+
+    let deleted_at = match data.questions.get(&id).and_then(|question| question.deleted_at) {
+      Some(deleted_at) => deleted_at,
+      None => return Err(ApiError::QuestionNotFoundError(id)),
+    };
+
+    // answers deleted on their own before the question stay in the trash
+    for answer in data.answers.values_mut() {
+      if answer.question_id.0 == id && answer.deleted_at == Some(deleted_at) {
+        answer.deleted_at = None;
+      }
+    }
+
+    let question = data.questions.get_mut(&id).ok_or(ApiError::QuestionNotFoundError(id))?;
+    question.deleted_at = None;
+    Ok(question.clone())
+  }
+
+  async fn restore_answer(&self, id: i32) -> Result<Answer, ApiError> {
+    let mut data = self.data.write();
+
+    let question_id = match data.answers.get(&id).filter(|answer| answer.deleted_at.is_some()) {
+      Some(answer) => answer.question_id.0,
+      None => return Err(ApiError::AnswerNotFoundError(id)),
+    };
+    if !data.has_question(question_id) {
+      return Err(ApiError::QuestionNotFoundError(question_id));
+    }
+
+    let answer = data.answers.get_mut(&id).ok_or(ApiError::AnswerNotFoundError(id))?;
+    answer.deleted_at = None;
+    Ok(answer.clone())
+  }
+
+  async fn purge_trash(&self, retention: chrono::Duration) -> Result<Purged, ApiError> {
+    let mut data = self.data.write();
+    let deleted_before = Utc::now().naive_utc() - retention;
+    let expired = |deleted_at: Option<NaiveDateTime>| deleted_at.is_some_and(|deleted_at| deleted_at < deleted_before);
+
+    let questions: Vec<i32> = data.questions.values().filter(|question| expired(question.deleted_at)).map(|question| question.id.0).collect();
+    let answers: Vec<i32> = data
+      .answers
+      .values()
+      .filter(|answer| expired(answer.deleted_at) || questions.contains(&answer.question_id.0))
+      .map(|answer| answer.id.0)
+      .collect();
+
+    for id in &answers {
+      data.answers.remove(id);
+    }
+    for id in &questions {
+      data.questions.remove(id);
+      data.revisions.remove(id);
+    }
+    data.answer_votes.retain(|(answer, _), _| !answers.contains(answer));
+    data.question_votes.retain(|(question, _), _| !questions.contains(question));
+    data.reviews.retain(|_, review| {
+      !review.question_id.as_ref().is_some_and(|question| questions.contains(&question.0))
+        && !review.answer_id.as_ref().is_some_and(|answer| answers.contains(&answer.0))
+    });
+
+    Ok(Purged {
+      questions: questions.len() as u64,
+      answers: answers.len() as u64,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn reviews_are_queued_with_the_content_or_not_at_all() {
+    let store = MemoryStore::new();
+    let question = NewQuestion { title: "t".to_string(), content: "c".to_string(), tags: None };
+    let question = store.add_question(question, AccountId(1), true).await.unwrap();
+
+    let answer = NewAnswer { content: "a".to_string(), question_id: QuestionId(999) };
+    assert!(store.add_answer(answer, AccountId(1), true).await.is_err());
+    let update = AnswerUpdate { content: "a".to_string() };
+    assert!(store.update_answer(update, 999, true).await.is_err());
+
+    let reviews = store.get_reviews().await.unwrap();
+    assert_eq!(reviews.len(), 1);
+    assert_eq!(reviews[0].question_id, Some(question.id));
+  }
+
+  /// a question with an answer and a vote on each, all three moved to the trash
+  async fn trashed_question(store: &MemoryStore) -> (i32, i32) {
+    let question = NewQuestion { title: "t".to_string(), content: "c".to_string(), tags: None };
+    let question = store.add_question(question, AccountId(1), false).await.unwrap();
+    let answer = NewAnswer { content: "a".to_string(), question_id: question.id.clone() };
+    let answer = store.add_answer(answer, AccountId(1), true).await.unwrap();
+    store.vote(VoteItem::Question(question.id.clone()), AccountId(2), Some(Direction::Up)).await.unwrap();
+    store.vote(VoteItem::Answer(answer.id.clone()), AccountId(2), Some(Direction::Up)).await.unwrap();
+    store.delete_question(question.id.0, OnQuestionDelete::Cascade).await.unwrap();
+    (question.id.0, answer.id.0)
+  }
+
+  #[tokio::test]
+  async fn purging_without_retention_empties_the_trash() {
+    let store = MemoryStore::new();
+    let (question, answer) = trashed_question(&store).await;
+
+    let purged = store.purge_trash(chrono::Duration::zero()).await.unwrap();
+    assert_eq!((purged.questions, purged.answers), (1, 1));
+
+    let data = store.data.read();
+    assert!(!data.questions.contains_key(&question) && !data.answers.contains_key(&answer));
+    assert!(data.revisions.is_empty() && data.reviews.is_empty());
+    assert!(data.question_votes.is_empty() && data.answer_votes.is_empty());
+  }
+
+  #[tokio::test]
+  async fn purging_keeps_what_was_deleted_after_the_cutoff() {
+    let store = MemoryStore::new();
+    let (old, _) = trashed_question(&store).await;
+    let (recent, _) = trashed_question(&store).await;
+    store.data.write().questions.get_mut(&old).unwrap().deleted_at = Some(Utc::now().naive_utc() - chrono::Duration::days(31));
+
+    let purged = store.purge_trash(chrono::Duration::days(30)).await.unwrap();
+    assert_eq!((purged.questions, purged.answers), (1, 1));
+
+    let trash = store.get_trash().await.unwrap();
+    assert_eq!(trash.questions.iter().map(|question| question.id.0).collect::<Vec<_>>(), vec![recent]);
+    assert_eq!(trash.answers.len(), 1);
+  }
+}
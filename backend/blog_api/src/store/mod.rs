@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use error_handler::ApiError;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use crate::config::OnQuestionDelete;
+use crate::types::account::{Account, AccountId, Role};
+use crate::types::answer::{Answer, AnswerId, AnswerUpdate, NewAnswer};
+use crate::types::filter::{QuestionFilter, QuestionSort};
+use crate::types::pagination::{CursorPagination, Page};
+use crate::types::question::{NewQuestion, Question, QuestionPatch};
+use crate::types::review::Review;
+use crate::types::revision::Revision;
+use crate::types::tag::{Tag, TagSort};
+use crate::types::trash::{Purged, Trash};
+use crate::types::vote::{Direction, VoteItem, Votes};
+
+pub mod memory;
+pub mod postgres;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+  pub size: u32,
+  pub idle: usize,
+}
+
+/// storage backend behind the routes, implemented for postgres and in memory.
+/// Missing rows are reported with the matching `*NotFoundError` variant,
+/// deleted rows sit in the trash and count as missing everywhere else.
+#[async_trait]
+pub trait Store: Clone + Debug + Send + Sync + 'static {
+  /// cheapest round trip to the storage
+  async fn ping(&self) -> Result<(), ApiError>;
+  /// versions of migrations missing from the schema or changed since they were applied
+  async fn pending_migrations(&self) -> Result<Vec<i64>, ApiError>;
+  /// connection pool usage, `None` for storage without a pool
+  fn pool_stats(&self) -> Option<PoolStats> {
+    None
+  }
+  /// waits for borrowed connections to come back and closes the pool, on shutdown
+  async fn close(&self) {}
+
+  /// questions ranked by search relevance, then by id. `QuestionSort::Score` puts the score first
+  async fn get_questions(&self, limit: Option<i32>, offset: i32, filter: QuestionFilter, sort: QuestionSort) -> Result<Vec<Question>, ApiError>;
+  /// questions ordered by `(created_at, id)`, or like `get_questions` and paged by offset
+  /// when searching or sorting by score
+  async fn get_questions_page(&self, pagination: CursorPagination, filter: QuestionFilter, sort: QuestionSort) -> Result<Page<Question>, ApiError>;
+  async fn get_tags(&self, sort: TagSort) -> Result<Vec<Tag>, ApiError>;
+  async fn get_question(&self, id: i32) -> Result<Question, ApiError>;
+  /// stores the question and its first revision, queued for review with `needs_review`
+  async fn add_question(&self, new_question: NewQuestion, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError>;
+  /// saves the result as a new revision by `account_id`, queued for review with `needs_review`
+  async fn update_question(&self, question: NewQuestion, id: i32, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError>;
+  /// saves the result as a new revision by `account_id`, queued for review with `needs_review`
+  async fn patch_question(&self, patch: QuestionPatch, id: i32, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError>;
+  /// moves the question to the trash in one transaction and returns the answers moved with it.
+  /// With `OnQuestionDelete::Block` answers fail it with `QuestionHasAnswersError`
+  async fn delete_question(&self, id: i32, on_answers: OnQuestionDelete) -> Result<Vec<AnswerId>, ApiError>;
+  async fn get_question_owner(&self, id: i32) -> Result<Option<AccountId>, ApiError>;
+  /// every saved version of a question, oldest first
+  async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, ApiError>;
+  async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, ApiError>;
+
+  /// answers of a question, highest score first, then oldest first
+  async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, ApiError>;
+  /// stores the answer, queued for review in the same transaction with `needs_review`
+  async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId, needs_review: bool) -> Result<Answer, ApiError>;
+  async fn update_answer(&self, update: AnswerUpdate, id: i32, needs_review: bool) -> Result<Answer, ApiError>;
+  async fn delete_answer(&self, id: i32) -> Result<bool, ApiError>;
+  async fn get_answer_owner(&self, id: i32) -> Result<Option<AccountId>, ApiError>;
+
+  /// fails with `DuplicateAccountError` when the e-mail is taken
+  async fn add_account(&self, account: Account) -> Result<Account, ApiError>;
+  async fn get_account(&self, email: &str) -> Result<Option<Account>, ApiError>;
+  async fn get_role(&self, account_id: AccountId) -> Result<Role, ApiError>;
+
+  /// reviews of items that still exist, oldest first
+  async fn get_reviews(&self) -> Result<Vec<Review>, ApiError>;
+
+  /// sets, changes or with `None` retracts the vote of `account_id`, the score follows in the same transaction
+  async fn vote(&self, item: VoteItem, account_id: AccountId, direction: Option<Direction>) -> Result<Votes, ApiError>;
+
+  async fn get_trash(&self) -> Result<Trash, ApiError>;
+  /// takes the question out of the trash, with the answers deleted along with it
+  async fn restore_question(&self, id: i32) -> Result<Question, ApiError>;
+  /// fails with `QuestionNotFoundError` while the question is in the trash
+  async fn restore_answer(&self, id: i32) -> Result<Answer, ApiError>;
+  /// hard deletes everything moved to the trash more than `retention` ago,
+  /// measured by the clock that set `deleted_at`
+  async fn purge_trash(&self, retention: chrono::Duration) -> Result<Purged, ApiError>;
+}
+
+/// empties the trash of items older than `retention` every `interval`, runs until the process ends
+pub async fn purge_trash_periodically<S: Store>(store: S, retention: chrono::Duration, interval: Duration) {
+  let mut ticks = tokio::time::interval(interval);
+  ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+  loop {
+    ticks.tick().await;
+
+    match store.purge_trash(retention).await {
+      Ok(Purged { questions: 0, answers: 0 }) => (),
+      Ok(purged) => tracing::event!(
+        tracing::Level::INFO,
+        "purged {} question(s) and {} answer(s) deleted more than {} day(s) ago",
+        purged.questions, purged.answers, retention.num_days(),
+      ),
+      Err(e) => tracing::event!(tracing::Level::WARN, "could not purge the trash: {}", e),
+    }
+  }
+}
@@ -0,0 +1,1016 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sqlx::{Connection, PgConnection, PgPool, Postgres, Row, Transaction, pool::PoolConnection, postgres::{PgPoolOptions, PgRow}};
+use std::io;
+use std::time::{Duration, Instant};
+use crate::config::{DatabaseConfig, OnQuestionDelete};
+use crate::metrics;
+use crate::migrate;
+use sqlx::migrate::MigrateError;
+use crate::store::{PoolStats, Store};
+use crate::types::account::{Account, AccountId, Role};
+use crate::types::answer::{Answer, AnswerId, AnswerUpdate, NewAnswer};
+use crate::types::filter::{QuestionFilter, QuestionSort};
+use crate::types::pagination::{Cursor, CursorKey, CursorPagination, Page};
+use crate::types::question::{Question, QuestionId, QuestionPatch, NewQuestion};
+use crate::types::review::{Review, ReviewItem};
+use crate::types::revision::Revision;
+use crate::types::tag::{Tag, TagSort};
+use crate::types::trash::{Purged, Trash};
+use crate::types::vote::{Direction, VoteItem, Votes};
+use error_handler::ApiError;
+
+/// exit code used when the database can't be reached at startup, `EX_UNAVAILABLE` from sysexits.h
+pub const EXIT_DATABASE: i32 = 69;
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// postgres error code raised when a unique constraint is broken
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// tries at numbering a revision before a conflict fails the change
+const REVISION_ATTEMPTS: u32 = 3;
+
+/// questions after the `($3, $4)` keyset, oldest first
+const QUESTIONS_AFTER: &str = "SELECT * FROM questions
+  WHERE deleted_at IS NULL
+    AND ($1::text[] IS NULL OR tags && $1)
+    AND ($2::text[] IS NULL OR tags @> $2)
+    AND ($3::timestamp IS NULL OR (created_at, id) > ($3, $4))
+  ORDER BY created_at, id
+  LIMIT $5";
+
+/// questions before the `($3, $4)` keyset, newest first
+const QUESTIONS_BEFORE: &str = "SELECT * FROM questions
+  WHERE deleted_at IS NULL
+    AND ($1::text[] IS NULL OR tags && $1)
+    AND ($2::text[] IS NULL OR tags @> $2)
+    AND (created_at, id) < ($3, $4)
+  ORDER BY created_at DESC, id DESC
+  LIMIT $5";
+
+/// counts pool timeouts, `handle_errors` logs the query error itself
+fn query_error(e: sqlx::Error) -> ApiError {
+  if let sqlx::Error::PoolTimedOut = e {
+    metrics::count_pool_timeout();
+  }
+
+  tracing::event!(tracing::Level::DEBUG, "{:?}", e);
+  ApiError::DatabaseQueryError(e)
+}
+
+fn review_from_row(row: PgRow) -> Review {
+  Review {
+    id: row.get("id"),
+    question_id: row.get::<Option<i32>, _>("question_id").map(QuestionId),
+    answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+    created_at: row.get("created_at"),
+  }
+}
+
+fn question_from_row(row: PgRow) -> Question {
+  Question {
+    id: QuestionId(row.get("id")),
+    title: row.get("title"),
+    content: row.get("content"),
+    tags: row.get("tags"),
+    created_at: row.get("created_at"),
+    updated_at: row.get("updated_at"),
+    account_id: row.get::<Option<i32>, _>("account_id").map(AccountId),
+    score: row.get("score"),
+    deleted_at: row.get("deleted_at"),
+  }
+}
+
+fn revision_from_row(row: PgRow) -> Revision {
+  Revision {
+    question_id: QuestionId(row.get("question_id")),
+    revision: row.get("revision"),
+    title: row.get("title"),
+    content: row.get("content"),
+    tags: row.get("tags"),
+    account_id: row.get::<Option<i32>, _>("account_id").map(AccountId),
+    created_at: row.get("created_at"),
+  }
+}
+
+/// saves `question` as its next revision, in the transaction that changed it.
+/// The changed question row stays locked until then, so numbers should not collide. When
+/// another transaction took the number anyway, the insert is skipped and tried again with
+/// the next one, only the last attempt fails on the conflict.
+async fn add_revision(tx: &mut Transaction<'_, Postgres>, question: &Question, account_id: AccountId) -> Result<(), ApiError> {
+  let mut attempt = 1;
+
+  loop {
+    let on_conflict = if attempt < REVISION_ATTEMPTS { "ON CONFLICT (question_id, revision) DO NOTHING" } else { "" };
+    let insert = format!(
+      "INSERT INTO question_revisions (question_id, revision, title, content, tags, account_id, created_at)
+       SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3, $4, $5, $6 FROM question_revisions WHERE question_id = $1
+       {}",
+      on_conflict,
+    );
+
+    match sqlx::query(&insert)
+      .bind(question.id.0)
+      .bind(&question.title)
+      .bind(&question.content)
+      .bind(&question.tags)
+      .bind(account_id.0)
+      .bind(question.updated_at)
+      .execute(&mut *tx)
+      .await {
+        Ok(res) if res.rows_affected() > 0 => return Ok(()),
+        Ok(_) => attempt += 1,
+        Err(e) => return Err(query_error(e)),
+      }
+  }
+}
+
+/// flags an item stored while moderation was unavailable, in the transaction that stored it
+async fn queue_review(tx: &mut Transaction<'_, Postgres>, item: ReviewItem) -> Result<(), ApiError> {
+  let (question_id, answer_id) = match item {
+    ReviewItem::Question(QuestionId(id)) => (Some(id), None),
+    ReviewItem::Answer(AnswerId(id)) => (None, Some(id)),
+  };
+
+  match sqlx::query("INSERT INTO moderation_reviews (question_id, answer_id) VALUES ($1, $2)")
+    .bind(question_id)
+    .bind(answer_id)
+    .execute(&mut *tx)
+    .await {
+      Ok(_) => Ok(()),
+      Err(e) => Err(query_error(e)),
+    }
+}
+
+fn answer_from_row(row: PgRow) -> Answer {
+  Answer {
+    id: AnswerId(row.get("id")),
+    content: row.get("content"),
+    question_id: QuestionId(row.get("corresponding_question")),
+    account_id: row.get::<Option<i32>, _>("account_id").map(AccountId),
+    score: row.get("score"),
+    deleted_at: row.get("deleted_at"),
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgStore {
+  pub connection: PgPool,
+}
+
+impl PgStore {
+  /// connects, retrying with backoff until `connect_deadline` has passed.
+  /// The last error is returned when the database never answered.
+  pub async fn connect(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+    let deadline = Instant::now() + Duration::from_secs(config.connect_deadline);
+    let mut delay = FIRST_RETRY_DELAY;
+
+    loop {
+      let timeout = deadline
+        .saturating_duration_since(Instant::now())
+        .clamp(FIRST_RETRY_DELAY, Duration::from_secs(config.acquire_timeout));
+
+      match probe(&config.url, timeout).await {
+        Ok(()) => {
+          let pool = pool_options(config).connect(&config.url).await?;
+          return Ok(PgStore { connection: pool });
+        }
+        Err(e) if Instant::now() + delay < deadline => {
+          tracing::event!(tracing::Level::WARN, "database not reachable, retrying in {:?}: {}", delay, e);
+          tokio::time::sleep(delay).await;
+          delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  /// pool that only connects on first use, so the server can start while the database is down
+  pub fn connect_lazy(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+    let pool = pool_options(config).connect_lazy(&config.url)?;
+    Ok(PgStore { connection: pool })
+  }
+
+  /// returns once the database accepts connections, retrying with backoff forever
+  pub async fn wait_until_reachable(&self, config: &DatabaseConfig) {
+    let mut delay = FIRST_RETRY_DELAY;
+
+    while let Err(e) = probe(&config.url, Duration::from_secs(config.acquire_timeout)).await {
+      tracing::event!(tracing::Level::WARN, "database not reachable, retrying in {:?}: {}", delay, e);
+      tokio::time::sleep(delay).await;
+      delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+  }
+
+  /// a connection from the pool, the wait for it is recorded in `db_pool_acquire_seconds`
+  async fn acquire(&self) -> Result<PoolConnection<Postgres>, ApiError> {
+    let started = Instant::now();
+    let connection = self.connection.acquire().await;
+    metrics::observe_pool_acquire(started.elapsed());
+    connection.map_err(query_error)
+  }
+}
+
+/// opens and closes a single connection. Unlike the pool, which only
+/// reports a timeout, this tells why the database can't be reached.
+async fn probe(url: &str, timeout: Duration) -> Result<(), sqlx::Error> {
+  match tokio::time::timeout(timeout, PgConnection::connect(url)).await {
+    Ok(connection) => connection?.close().await,
+    Err(_) => Err(sqlx::Error::Io(io::Error::new(io::ErrorKind::TimedOut, "connection attempt timed out"))),
+  }
+}
+
+fn pool_options(config: &DatabaseConfig) -> PgPoolOptions {
+  PgPoolOptions::new()
+    .max_connections(config.max_connections)
+    .min_connections(config.min_connections)
+    .connect_timeout(Duration::from_secs(config.acquire_timeout))
+}
+
+#[async_trait]
+impl Store for PgStore {
+  async fn ping(&self) -> Result<(), ApiError> {
+    match sqlx::query("SELECT 1").execute(&mut *self.acquire().await?).await {
+      Ok(_) => Ok(()),
+      Err(e) => Err(query_error(e)),
+    }
+  }
+
+  fn pool_stats(&self) -> Option<PoolStats> {
+    Some(PoolStats {
+      size: self.connection.size(),
+      idle: self.connection.num_idle(),
+    })
+  }
+
+  async fn close(&self) {
+    self.connection.close().await;
+  }
+
+  async fn pending_migrations(&self) -> Result<Vec<i64>, ApiError> {
+    match migrate::status(&self.connection).await {
+      Ok(migrations) => Ok(migrations
+        .into_iter()
+        .filter(|migration| !migration.applied || migration.checksum_mismatch)
+        .map(|migration| migration.version)
+        .collect()),
+      Err(MigrateError::Execute(e)) => Err(query_error(e)),
+      Err(e) => Err(query_error(e.into())),
+    }
+  }
+
+  async fn get_questions(&self, limit: Option<i32>, offset: i32, filter: QuestionFilter, sort: QuestionSort) -> Result<Vec<Question>, ApiError> {
+    let order = match (sort, filter.search.is_some()) {
+      // the order of the questions_score index
+      (QuestionSort::Score, false) => "score DESC, id",
+      (QuestionSort::Score, true) => "score DESC, ts_rank(search, query) DESC, id",
+      (QuestionSort::Created, _) => "ts_rank(search, query) DESC, id",
+    };
+
+    match sqlx::query(&format!(
+      "SELECT questions.* FROM questions, websearch_to_tsquery('english', $3) AS query
+       WHERE deleted_at IS NULL
+         AND ($3 IS NULL OR search @@ query)
+         AND ($4::text[] IS NULL OR tags && $4)
+         AND ($5::text[] IS NULL OR tags @> $5)
+       ORDER BY {}
+       LIMIT $1 OFFSET $2",
+      order,
+    ))
+      .bind(limit)
+      .bind(offset)
+      .bind(filter.search)
+      .bind(filter.tags_any)
+      .bind(filter.tags_all)
+      .map(question_from_row)
+      .fetch_all(&mut *self.acquire().await?).await {
+        Ok(questions) => Ok(questions),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_questions_page(&self, pagination: CursorPagination, filter: QuestionFilter, sort: QuestionSort) -> Result<Page<Question>, ApiError> {
+    let foreign_cursor = || ApiError::InvalidParamError("cursor does not belong to this query".to_string());
+    let limit = pagination.limit;
+
+    // relevance and score rankings have no stable keyset, so they are paged by offset
+    if filter.search.is_some() || sort == QuestionSort::Score {
+      let offset = match pagination.cursor {
+        None => 0,
+        Some(Cursor::Offset(offset)) => offset,
+        Some(_) => return Err(foreign_cursor()),
+      };
+
+      let questions = self.get_questions(Some(limit + 1), offset, filter, sort).await?;
+      return Ok(Page::from_offset(questions, limit, offset));
+    }
+
+    let (query, key) = match pagination.cursor {
+      None => (QUESTIONS_AFTER, None),
+      Some(Cursor::After(key)) => (QUESTIONS_AFTER, Some(key)),
+      Some(Cursor::Before(key)) => (QUESTIONS_BEFORE, Some(key)),
+      Some(Cursor::Offset(_)) => return Err(foreign_cursor()),
+    };
+
+    match sqlx::query(query)
+      .bind(filter.tags_any)
+      .bind(filter.tags_all)
+      .bind(key.map(|key| key.created_at))
+      .bind(key.map(|key| key.id))
+      .bind(limit + 1)
+      .map(|row: PgRow| (
+        CursorKey {
+          created_at: row.get("created_at"),
+          id: row.get("id"),
+        },
+        question_from_row(row),
+      ))
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(rows) => Ok(Page::from_keyset(rows, limit, pagination.cursor)),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_tags(&self, sort: TagSort) -> Result<Vec<Tag>, ApiError> {
+    let query = match sort {
+      TagSort::Name => "SELECT tag, COUNT(DISTINCT id) AS questions FROM questions, unnest(tags) AS tag WHERE deleted_at IS NULL GROUP BY tag ORDER BY tag",
+      TagSort::Popularity => "SELECT tag, COUNT(DISTINCT id) AS questions FROM questions, unnest(tags) AS tag WHERE deleted_at IS NULL GROUP BY tag ORDER BY questions DESC, tag",
+    };
+
+    match sqlx::query(query)
+      .map(|row: PgRow| Tag {
+        name: row.get("tag"),
+        questions: row.get("questions"),
+      })
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(tags) => Ok(tags),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_question(&self, id: i32) -> Result<Question, ApiError> {
+    match sqlx::query("SELECT * FROM questions WHERE id = $1 AND deleted_at IS NULL")
+      .bind(id)
+      .map(question_from_row)
+      .fetch_optional(&mut *self.acquire().await?)
+      .await {
+        Ok(Some(question)) => Ok(question),
+        Ok(None) => Err(ApiError::QuestionNotFoundError(id)),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn add_question(&self, new_question: NewQuestion, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    let question = match sqlx::query("INSERT INTO questions (title, content, tags, account_id) VALUES ($1, $2, $3, $4) RETURNING id, title, content, tags, created_at, updated_at, account_id, score, deleted_at")
+      .bind(new_question.title)
+      .bind(new_question.content)
+      .bind(new_question.tags)
+      .bind(account_id.0)
+      .map(question_from_row)
+      .fetch_one(&mut tx)
+      .await {
+        Ok(question) => question,
+        Err(e) => return Err(query_error(e)),
+      };
+
+    add_revision(&mut tx, &question, account_id).await?;
+    if needs_review {
+      queue_review(&mut tx, ReviewItem::Question(question.id.clone())).await?;
+    }
+    tx.commit().await.map_err(query_error)?;
+    Ok(question)
+  }
+
+  async fn update_question(&self, question: NewQuestion, id: i32, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    let question = match sqlx::query("UPDATE questions SET title = $1, content = $2, tags = $3, updated_at = NOW() WHERE id = $4 AND deleted_at IS NULL RETURNING id, title, content, tags, created_at, updated_at, account_id, score, deleted_at")
+      .bind(question.title)
+      .bind(question.content)
+      .bind(question.tags)
+      .bind(id)
+      .map(question_from_row)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(question)) => question,
+        Ok(None) => return Err(ApiError::QuestionNotFoundError(id)),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    add_revision(&mut tx, &question, account_id).await?;
+    if needs_review {
+      queue_review(&mut tx, ReviewItem::Question(question.id.clone())).await?;
+    }
+    tx.commit().await.map_err(query_error)?;
+    Ok(question)
+  }
+
+  async fn patch_question(&self, patch: QuestionPatch, id: i32, account_id: AccountId, needs_review: bool) -> Result<Question, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    let question = match sqlx::query(
+      "UPDATE questions
+       SET title = COALESCE($1, title), content = COALESCE($2, content),
+           tags = CASE WHEN $3 THEN $4 ELSE tags END, updated_at = NOW()
+       WHERE id = $5 AND deleted_at IS NULL
+       RETURNING id, title, content, tags, created_at, updated_at, account_id, score, deleted_at"
+    )
+      .bind(patch.title)
+      .bind(patch.content)
+      .bind(patch.tags.is_some())
+      .bind(patch.tags.flatten())
+      .bind(id)
+      .map(question_from_row)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(question)) => question,
+        Ok(None) => return Err(ApiError::QuestionNotFoundError(id)),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    add_revision(&mut tx, &question, account_id).await?;
+    if needs_review {
+      queue_review(&mut tx, ReviewItem::Question(question.id.clone())).await?;
+    }
+    tx.commit().await.map_err(query_error)?;
+    Ok(question)
+  }
+
+  async fn delete_question(&self, id: i32, on_answers: OnQuestionDelete) -> Result<Vec<AnswerId>, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    // locking the question keeps new answers out until the transaction ends
+    match sqlx::query("SELECT id FROM questions WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+      .bind(id)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::QuestionNotFoundError(id)),
+        Err(e) => return Err(query_error(e)),
+      }
+
+    let answers = match sqlx::query("SELECT id FROM answers WHERE corresponding_question = $1 AND deleted_at IS NULL ORDER BY id")
+      .bind(id)
+      .map(|row: PgRow| row.get::<i32, _>("id"))
+      .fetch_all(&mut tx)
+      .await {
+        Ok(answers) => answers,
+        Err(e) => return Err(query_error(e)),
+      };
+
+    if !answers.is_empty() && on_answers == OnQuestionDelete::Block {
+      return Err(ApiError::QuestionHasAnswersError { id, answers });
+    }
+
+    // NOW() is the same for the whole transaction, restoring relies on it
+    if let Err(e) = sqlx::query("UPDATE answers SET deleted_at = NOW() WHERE corresponding_question = $1 AND deleted_at IS NULL")
+      .bind(id)
+      .execute(&mut tx)
+      .await {
+        return Err(query_error(e));
+      }
+
+    match sqlx::query("UPDATE questions SET deleted_at = NOW() WHERE id = $1")
+      .bind(id)
+      .execute(&mut tx)
+      .await {
+        Ok(_) => (),
+        Err(e) => return Err(query_error(e)),
+      }
+
+    tx.commit().await.map_err(query_error)?;
+    Ok(answers.into_iter().map(AnswerId).collect())
+  }
+
+  async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId, needs_review: bool) -> Result<Answer, ApiError> {
+    let question_id = new_answer.question_id.0;
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    // FOR SHARE waits for a concurrent delete and then sees the question in the trash
+    let answer = match sqlx::query(
+      "INSERT INTO answers (content, corresponding_question, account_id)
+       SELECT $1, $2, $3 WHERE EXISTS (SELECT 1 FROM questions WHERE id = $2 AND deleted_at IS NULL FOR SHARE)
+       RETURNING id, content, corresponding_question, account_id, score, deleted_at"
+    )
+      .bind(new_answer.content)
+      .bind(question_id)
+      .bind(account_id.0)
+      .map(answer_from_row)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(answer)) => answer,
+        Ok(None) => return Err(ApiError::QuestionNotFoundError(question_id)),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    if needs_review {
+      queue_review(&mut tx, ReviewItem::Answer(answer.id.clone())).await?;
+    }
+    tx.commit().await.map_err(query_error)?;
+    Ok(answer)
+  }
+
+  async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, ApiError> {
+    self.get_question(question_id).await?;
+
+    match sqlx::query("SELECT * FROM answers WHERE corresponding_question = $1 AND deleted_at IS NULL ORDER BY score DESC, created_at, id LIMIT $2 OFFSET $3")
+      .bind(question_id)
+      .bind(limit)
+      .bind(offset)
+      .map(answer_from_row)
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(answers) => Ok(answers),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn add_account(&self, account: Account) -> Result<Account, ApiError> {
+    match sqlx::query("INSERT INTO accounts (email, password) VALUES ($1, $2) RETURNING id, email, password")
+      .bind(account.email)
+      .bind(account.password)
+      .map(|row: PgRow| Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+      })
+      .fetch_one(&mut *self.acquire().await?)
+      .await {
+        Ok(account) => Ok(account),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+          Err(ApiError::DuplicateAccountError)
+        }
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_account(&self, email: &str) -> Result<Option<Account>, ApiError> {
+    match sqlx::query("SELECT id, email, password FROM accounts WHERE email = $1")
+      .bind(email)
+      .map(|row: PgRow| Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+      })
+      .fetch_optional(&mut *self.acquire().await?)
+      .await {
+        Ok(account) => Ok(account),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn update_answer(&self, update: AnswerUpdate, id: i32, needs_review: bool) -> Result<Answer, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    let answer = match sqlx::query("UPDATE answers SET content = $1 WHERE id = $2 AND deleted_at IS NULL RETURNING id, content, corresponding_question, account_id, score, deleted_at")
+      .bind(update.content)
+      .bind(id)
+      .map(answer_from_row)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(answer)) => answer,
+        Ok(None) => return Err(ApiError::AnswerNotFoundError(id)),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    if needs_review {
+      queue_review(&mut tx, ReviewItem::Answer(answer.id.clone())).await?;
+    }
+    tx.commit().await.map_err(query_error)?;
+    Ok(answer)
+  }
+
+  async fn delete_answer(&self, id: i32) -> Result<bool, ApiError> {
+    match sqlx::query("UPDATE answers SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL")
+      .bind(id)
+      .execute(&mut *self.acquire().await?)
+      .await {
+        Ok(res) if res.rows_affected() == 0 => Err(ApiError::AnswerNotFoundError(id)),
+        Ok(_) => Ok(true),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_question_owner(&self, id: i32) -> Result<Option<AccountId>, ApiError> {
+    match sqlx::query("SELECT account_id FROM questions WHERE id = $1 AND deleted_at IS NULL")
+      .bind(id)
+      .map(|row: PgRow| row.get::<Option<i32>, _>("account_id").map(AccountId))
+      .fetch_optional(&mut *self.acquire().await?)
+      .await {
+        Ok(Some(owner)) => Ok(owner),
+        Ok(None) => Err(ApiError::QuestionNotFoundError(id)),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, ApiError> {
+    self.get_question(question_id).await?;
+
+    match sqlx::query("SELECT * FROM question_revisions WHERE question_id = $1 ORDER BY revision")
+      .bind(question_id)
+      .map(revision_from_row)
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(revisions) => Ok(revisions),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, ApiError> {
+    self.get_question(question_id).await?;
+
+    match sqlx::query("SELECT * FROM question_revisions WHERE question_id = $1 AND revision = $2")
+      .bind(question_id)
+      .bind(revision)
+      .map(revision_from_row)
+      .fetch_optional(&mut *self.acquire().await?)
+      .await {
+        Ok(Some(revision)) => Ok(revision),
+        Ok(None) => Err(ApiError::RevisionNotFoundError { question: question_id, revision }),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_answer_owner(&self, id: i32) -> Result<Option<AccountId>, ApiError> {
+    match sqlx::query("SELECT account_id FROM answers WHERE id = $1 AND deleted_at IS NULL")
+      .bind(id)
+      .map(|row: PgRow| row.get::<Option<i32>, _>("account_id").map(AccountId))
+      .fetch_optional(&mut *self.acquire().await?)
+      .await {
+        Ok(Some(owner)) => Ok(owner),
+        Ok(None) => Err(ApiError::AnswerNotFoundError(id)),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_role(&self, account_id: AccountId) -> Result<Role, ApiError> {
+    match sqlx::query("SELECT role FROM accounts WHERE id = $1")
+      .bind(account_id.0)
+      .map(|row: PgRow| match row.get::<&str, _>("role") {
+        "admin" => Role::Admin,
+        _ => Role::User,
+      })
+      .fetch_optional(&mut *self.acquire().await?)
+      .await {
+        Ok(Some(role)) => Ok(role),
+        // the token outlived its account
+        Ok(None) => Err(ApiError::UnauthorizedError),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn get_reviews(&self) -> Result<Vec<Review>, ApiError> {
+    match sqlx::query("SELECT id, question_id, answer_id, created_at FROM moderation_reviews ORDER BY created_at, id")
+      .map(review_from_row)
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(reviews) => Ok(reviews),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn vote(&self, item: VoteItem, account_id: AccountId, direction: Option<Direction>) -> Result<Votes, ApiError> {
+    let (items, votes, column, id, not_found) = match item {
+      VoteItem::Question(QuestionId(id)) => ("questions", "question_votes", "question_id", id, ApiError::QuestionNotFoundError(id)),
+      VoteItem::Answer(AnswerId(id)) => ("answers", "answer_votes", "answer_id", id, ApiError::AnswerNotFoundError(id)),
+    };
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    // the lock on the item keeps concurrent votes from losing score updates
+    match sqlx::query(&format!("SELECT id FROM {} WHERE id = $1 AND deleted_at IS NULL FOR UPDATE", items))
+      .bind(id)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(not_found),
+        Err(e) => return Err(query_error(e)),
+      }
+
+    let previous = match sqlx::query(&format!("SELECT value FROM {} WHERE {} = $1 AND account_id = $2", votes, column))
+      .bind(id)
+      .bind(account_id.0)
+      .map(|row: PgRow| row.get::<i16, _>("value") as i32)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(previous) => previous.unwrap_or(0),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    let res = match direction {
+      Some(direction) => sqlx::query(&format!(
+        "INSERT INTO {votes} ({column}, account_id, value) VALUES ($1, $2, $3)
+         ON CONFLICT ({column}, account_id) DO UPDATE SET value = EXCLUDED.value, created_at = NOW()",
+        votes = votes,
+        column = column,
+      ))
+        .bind(id)
+        .bind(account_id.0)
+        .bind(direction.value() as i16)
+        .execute(&mut tx)
+        .await,
+      None => sqlx::query(&format!("DELETE FROM {} WHERE {} = $1 AND account_id = $2", votes, column))
+        .bind(id)
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await,
+    };
+    if let Err(e) = res {
+      return Err(query_error(e));
+    }
+
+    let score = match sqlx::query(&format!("UPDATE {} SET score = score + $2 WHERE id = $1 RETURNING score", items))
+      .bind(id)
+      .bind(direction.map_or(0, Direction::value) - previous)
+      .map(|row: PgRow| row.get::<i32, _>("score"))
+      .fetch_one(&mut tx)
+      .await {
+        Ok(score) => score,
+        Err(e) => return Err(query_error(e)),
+      };
+
+    tx.commit().await.map_err(query_error)?;
+    Ok(Votes { score, vote: direction })
+  }
+
+  async fn get_trash(&self) -> Result<Trash, ApiError> {
+    let questions = match sqlx::query("SELECT * FROM questions WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id")
+      .map(question_from_row)
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(questions) => questions,
+        Err(e) => return Err(query_error(e)),
+      };
+
+    match sqlx::query("SELECT * FROM answers WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id")
+      .map(answer_from_row)
+      .fetch_all(&mut *self.acquire().await?)
+      .await {
+        Ok(answers) => Ok(Trash { questions, answers }),
+        Err(e) => Err(query_error(e)),
+      }
+  }
+
+  async fn restore_question(&self, id: i32) -> Result<Question, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    let deleted_at: NaiveDateTime = match sqlx::query("SELECT deleted_at FROM questions WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE")
+      .bind(id)
+      .map(|row: PgRow| row.get("deleted_at"))
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(deleted_at)) => deleted_at,
+        Ok(None) => return Err(ApiError::QuestionNotFoundError(id)),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    // answers deleted on their own before the question stay in the trash
+    if let Err(e) = sqlx::query("UPDATE answers SET deleted_at = NULL WHERE corresponding_question = $1 AND deleted_at = $2")
+      .bind(id)
+      .bind(deleted_at)
+      .execute(&mut tx)
+      .await {
+        return Err(query_error(e));
+      }
+
+    let question = match sqlx::query("UPDATE questions SET deleted_at = NULL WHERE id = $1 RETURNING id, title, content, tags, created_at, updated_at, account_id, score, deleted_at")
+      .bind(id)
+      .map(question_from_row)
+      .fetch_one(&mut tx)
+      .await {
+        Ok(question) => question,
+        Err(e) => return Err(query_error(e)),
+      };
+
+    tx.commit().await.map_err(query_error)?;
+    Ok(question)
+  }
+
+  async fn restore_answer(&self, id: i32) -> Result<Answer, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    let question_id: i32 = match sqlx::query("SELECT corresponding_question FROM answers WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE")
+      .bind(id)
+      .map(|row: PgRow| row.get("corresponding_question"))
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(question_id)) => question_id,
+        Ok(None) => return Err(ApiError::AnswerNotFoundError(id)),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    match sqlx::query("SELECT id FROM questions WHERE id = $1 AND deleted_at IS NULL FOR SHARE")
+      .bind(question_id)
+      .fetch_optional(&mut tx)
+      .await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::QuestionNotFoundError(question_id)),
+        Err(e) => return Err(query_error(e)),
+      }
+
+    let answer = match sqlx::query("UPDATE answers SET deleted_at = NULL WHERE id = $1 RETURNING id, content, corresponding_question, account_id, score, deleted_at")
+      .bind(id)
+      .map(answer_from_row)
+      .fetch_one(&mut tx)
+      .await {
+        Ok(answer) => answer,
+        Err(e) => return Err(query_error(e)),
+      };
+
+    tx.commit().await.map_err(query_error)?;
+    Ok(answer)
+  }
+
+  async fn purge_trash(&self, retention: chrono::Duration) -> Result<Purged, ApiError> {
+    let mut conn = self.acquire().await?;
+    let mut tx = conn.begin().await.map_err(query_error)?;
+
+    // deleted_at comes from NOW(), so the cutoff does too. Answers go first,
+    // including the ones of questions about to be purged
+    let answers = match sqlx::query(
+      "DELETE FROM answers
+       WHERE deleted_at < NOW() - $1::interval
+          OR corresponding_question IN (SELECT id FROM questions WHERE deleted_at < NOW() - $1::interval)"
+    )
+      .bind(retention)
+      .execute(&mut tx)
+      .await {
+        Ok(res) => res.rows_affected(),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    let questions = match sqlx::query("DELETE FROM questions WHERE deleted_at < NOW() - $1::interval")
+      .bind(retention)
+      .execute(&mut tx)
+      .await {
+        Ok(res) => res.rows_affected(),
+        Err(e) => return Err(query_error(e)),
+      };
+
+    tx.commit().await.map_err(query_error)?;
+    Ok(Purged { questions, answers })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// a migrated store on `BLOG_API_TEST_DATABASE_URL`, these tests pass without one
+  async fn store() -> Option<PgStore> {
+    let url = std::env::var("BLOG_API_TEST_DATABASE_URL").ok()?;
+    let store = PgStore { connection: PgPool::connect(&url).await.unwrap() };
+    migrate::up(&store.connection).await.unwrap();
+    Some(store)
+  }
+
+  async fn account(store: &PgStore) -> AccountId {
+    let email = format!("revisions-{}@example.com", rand::random::<u64>());
+    let account = Account { id: None, email, password: "hash".to_string() };
+    store.add_account(account).await.unwrap().id.unwrap()
+  }
+
+  fn new_question(title: &str) -> NewQuestion {
+    NewQuestion { title: title.to_string(), content: "content".to_string(), tags: None }
+  }
+
+  #[tokio::test]
+  async fn concurrent_edits_get_consecutive_revisions() {
+    let store = match store().await {
+      Some(store) => store,
+      None => return,
+    };
+    let account_id = account(&store).await;
+    let question = store.add_question(new_question("v1"), account_id, false).await.unwrap();
+
+    let edits: Vec<_> = (2..=9)
+      .map(|version| {
+        let store = store.clone();
+        let id = question.id.0;
+        tokio::spawn(async move { store.update_question(new_question(&format!("v{}", version)), id, account_id, false).await })
+      })
+      .collect();
+    for edit in edits {
+      edit.await.unwrap().unwrap();
+    }
+
+    let revisions = store.get_revisions(question.id.0).await.unwrap();
+    assert_eq!(revisions.iter().map(|revision| revision.revision).collect::<Vec<_>>(), (1..=9).collect::<Vec<_>>());
+  }
+
+  #[tokio::test]
+  async fn taken_revision_numbers_are_skipped() {
+    let store = match store().await {
+      Some(store) => store,
+      None => return,
+    };
+    let account_id = account(&store).await;
+    let question = store.add_question(new_question("v1"), account_id, false).await.unwrap();
+
+    // a transaction that holds revision 2 without the question row lock
+    let mut other = store.connection.begin().await.unwrap();
+    sqlx::query("INSERT INTO question_revisions (question_id, revision, title, content) VALUES ($1, 2, 'other', 'other')")
+      .bind(question.id.0)
+      .execute(&mut other)
+      .await
+      .unwrap();
+
+    let revising = {
+      let (store, question) = (store.clone(), question.clone());
+      tokio::spawn(async move {
+        let mut tx = store.connection.begin().await.unwrap();
+        add_revision(&mut tx, &question, account_id).await?;
+        tx.commit().await.map_err(query_error)
+      })
+    };
+    // the insert waits on the other transaction before it sees the conflict
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    other.commit().await.unwrap();
+    revising.await.unwrap().unwrap();
+
+    let revisions = store.get_revisions(question.id.0).await.unwrap();
+    let titles: Vec<(i32, &str)> = revisions.iter().map(|revision| (revision.revision, revision.title.as_str())).collect();
+    assert_eq!(titles, vec![(1, "v1"), (2, "other"), (3, "v1")]);
+  }
+
+  #[tokio::test]
+  async fn foreign_key_violations_are_told_apart_by_constraint_name() {
+    let store = match store().await {
+      Some(store) => store,
+      None => return,
+    };
+    let account_id = account(&store).await;
+    let question = store.add_question(new_question("answered"), account_id, false).await.unwrap();
+    let answer = NewAnswer { content: "answer".to_string(), question_id: question.id.clone() };
+    store.add_answer(answer, account_id, false).await.unwrap();
+
+    let res = sqlx::query("DELETE FROM questions WHERE id = $1").bind(question.id.0).execute(&store.connection).await;
+    let error = query_error(res.unwrap_err());
+    assert_eq!((error.status().as_u16(), error.kind()), (409, "still_referenced"));
+
+    let res = sqlx::query("INSERT INTO question_votes (question_id, account_id, value) VALUES ($1, $2, 1)")
+      .bind(i32::MAX)
+      .bind(account_id.0)
+      .execute(&store.connection)
+      .await;
+    let error = query_error(res.unwrap_err());
+    assert_eq!((error.status().as_u16(), error.kind()), (404, "reference_not_found"));
+  }
+
+  #[tokio::test]
+  async fn purging_without_retention_removes_trashed_rows() {
+    let store = match store().await {
+      Some(store) => store,
+      None => return,
+    };
+    let account_id = account(&store).await;
+    let question = store.add_question(new_question("trashed"), account_id, true).await.unwrap();
+    let answer = NewAnswer { content: "answer".to_string(), question_id: question.id.clone() };
+    let answer = store.add_answer(answer, account_id, true).await.unwrap();
+    store.vote(VoteItem::Answer(answer.id.clone()), account_id, Some(Direction::Up)).await.unwrap();
+    store.delete_question(question.id.0, OnQuestionDelete::Cascade).await.unwrap();
+
+    let purged = store.purge_trash(chrono::Duration::zero()).await.unwrap();
+    assert!(purged.questions >= 1 && purged.answers >= 1);
+
+    let trash = store.get_trash().await.unwrap();
+    assert!(trash.questions.iter().all(|trashed| trashed.id != question.id));
+    assert!(matches!(store.restore_question(question.id.0).await, Err(ApiError::QuestionNotFoundError(_))));
+  }
+
+  #[tokio::test]
+  async fn votes_move_the_score_once_per_account() {
+    let store = match store().await {
+      Some(store) => store,
+      None => return,
+    };
+    let (author, voter) = (account(&store).await, account(&store).await);
+    let question = store.add_question(new_question("voted"), author, false).await.unwrap();
+    let item = || VoteItem::Question(question.id.clone());
+
+    let scores = [
+      store.vote(item(), voter, Some(Direction::Up)).await.unwrap().score,
+      store.vote(item(), voter, Some(Direction::Up)).await.unwrap().score,
+      store.vote(item(), voter, Some(Direction::Down)).await.unwrap().score,
+      store.vote(item(), author, Some(Direction::Down)).await.unwrap().score,
+      store.vote(item(), voter, None).await.unwrap().score,
+      store.vote(item(), voter, None).await.unwrap().score,
+    ];
+    assert_eq!(scores, [1, 1, -1, -2, -1, -1]);
+    assert_eq!(store.get_question(question.id.0).await.unwrap().score, -1);
+  }
+}
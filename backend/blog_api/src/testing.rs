@@ -0,0 +1,116 @@
+//! fixtures shared by the route tests: the whole api over a memory store, driven with `warp::test`
+
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use warp::filters::BoxedFilter;
+use warp::http::{Response, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::test::RequestBuilder;
+use warp::Filter;
+
+use crate::config::{Config, ModerationBackend, StorageBackend};
+use crate::routes::health::Readiness;
+use crate::store::memory::MemoryStore;
+
+/// memory storage, no moderation and a valid token key
+pub fn config() -> Config {
+  let mut config = Config::default();
+  config.storage.backend = StorageBackend::Memory;
+  config.auth.token_key = "k".repeat(32);
+  config.moderation.backend = ModerationBackend::None;
+  config
+}
+
+/// every route of the api, with the store kept around to look behind it
+pub struct TestApi {
+  pub store: MemoryStore,
+  filter: BoxedFilter<(warp::reply::Response,)>,
+}
+
+impl TestApi {
+  pub fn new() -> Self {
+    TestApi::with_config(&config())
+  }
+
+  pub fn with_config(config: &Config) -> Self {
+    let store = MemoryStore::new();
+    let filter = crate::filters(store.clone(), config, Readiness::default())
+      .map(warp::Reply::into_response)
+      .boxed();
+    TestApi { store, filter }
+  }
+
+  pub async fn reply(&self, request: RequestBuilder) -> Response<Bytes> {
+    request.reply(&self.filter).await
+  }
+
+  /// status and json body of the response, `Value::Null` for bodies that aren't json
+  pub async fn send(&self, request: RequestBuilder) -> (StatusCode, Value) {
+    let res = request.reply(&self.filter).await;
+    let body = serde_json::from_slice(res.body()).unwrap_or(Value::Null);
+    (res.status(), body)
+  }
+
+  /// registers `email` and returns an `Authorization` header value for it
+  pub async fn sign_in(&self, email: &str) -> String {
+    let account = json!({ "email": email, "password": "password1" });
+    self.send(warp::test::request().method("POST").path("/registration").json(&account)).await;
+    let (status, token) = self.send(warp::test::request().method("POST").path("/login").json(&account)).await;
+    assert_eq!(status, StatusCode::OK);
+    format!("Bearer {}", token["token"].as_str().unwrap())
+  }
+
+  /// like `sign_in`, for an admin account
+  pub async fn sign_in_admin(&self, email: &str) -> String {
+    let token = self.sign_in(email).await;
+    self.store.promote(email);
+    token
+  }
+
+  /// asks a question as `token` and returns its id
+  pub async fn ask(&self, token: &str, title: &str) -> i64 {
+    let (status, question) = self.send(post_question(token, title)).await;
+    assert_eq!(status, StatusCode::OK);
+    question["id"].as_i64().unwrap()
+  }
+
+  /// answers `question_id` as `token` and returns the answer id
+  pub async fn answer(&self, token: &str, question_id: i64) -> i64 {
+    let (status, answer) = self.send(post_answer(token, question_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    answer["id"].as_i64().unwrap()
+  }
+}
+
+/// request sent with the `Authorization` header value returned by `sign_in`
+pub fn authorized(method: &str, path: &str, token: &str) -> RequestBuilder {
+  warp::test::request().method(method).path(path).header("authorization", token)
+}
+
+pub fn post_question(token: &str, title: &str) -> RequestBuilder {
+  authorized("POST", "/questions", token).json(&json!({ "title": title, "content": "content", "tags": ["rust"] }))
+}
+
+pub fn post_answer(token: &str, question_id: i64) -> RequestBuilder {
+  authorized("POST", "/answers", token)
+    .header("content-type", "application/x-www-form-urlencoded")
+    .body(format!("relationId={}&content=an+answer", question_id))
+}
+
+/// serves `status` with a json error body on a free local port,
+/// returns its base url and the number of requests it got
+pub async fn mock_service(status: u16) -> (String, Arc<AtomicUsize>) {
+  let calls = Arc::new(AtomicUsize::new(0));
+  let counter = calls.clone();
+  let status = StatusCode::from_u16(status).unwrap();
+
+  let service = warp::any().map(move || {
+    counter.fetch_add(1, Ordering::SeqCst);
+    warp::reply::with_status(warp::reply::json(&json!({ "message": "mocked" })), status)
+  });
+  let (address, server) = warp::serve(service).bind_ephemeral(([127, 0, 0, 1], 0));
+  tokio::spawn(server);
+
+  (format!("http://{}", address), calls)
+}
@@ -0,0 +1,39 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Account {
+  pub id: Option<AccountId>,
+  pub email: String,
+  #[serde(skip_serializing)]
+  pub password: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccountId(pub i32);
+
+/// what an account may do besides managing its own questions and answers
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  User,
+  /// may edit and delete anyone's questions and answers
+  Admin,
+}
+
+/// claims carried by a signed bearer token
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+  pub account_id: AccountId,
+  /// expiry, seconds since the unix epoch
+  pub exp: i64,
+  /// not valid before, seconds since the unix epoch
+  pub nbf: i64,
+}
+
+/// reply of a successful login
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthToken {
+  pub token: String,
+  pub expires_at: NaiveDateTime,
+}
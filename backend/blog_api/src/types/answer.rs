@@ -1,5 +1,7 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
+use crate::types::account::AccountId;
 use crate::types::question::QuestionId;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -7,7 +9,26 @@ pub struct Answer {
   pub id: AnswerId,
   pub content: String,
   pub question_id: QuestionId,
+  /// author of the answer, unset for answers given before accounts existed
+  pub account_id: Option<AccountId>,
+  /// upvotes minus downvotes
+  #[serde(default)]
+  pub score: i32,
+  /// set while the answer is in the trash
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
-pub struct AnswerId(pub String);
+pub struct AnswerId(pub i32);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewAnswer {
+  pub content: String,
+  pub question_id: QuestionId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnswerUpdate {
+  pub content: String,
+}
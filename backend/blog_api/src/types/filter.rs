@@ -0,0 +1,66 @@
+use error_handler::ApiError;
+use std::collections::HashMap;
+
+/// filters for the `/questions` route, got from query params
+#[derive(Debug, Default)]
+pub struct QuestionFilter {
+  /// full-text search over title and content, ranks results by relevance
+  pub search: Option<String>,
+  /// questions carrying at least one of these tags
+  pub tags_any: Option<Vec<String>>,
+  /// questions carrying every one of these tags
+  pub tags_all: Option<Vec<String>>,
+}
+
+/// extract filter params for the `/questions` route
+/// # Example query
+/// `/questions?q=borrow checker&limit=10&offset=0`
+///
+/// tag lists are comma separated:
+/// `/questions?tag=rust,warp` or `/questions?tags_all=rust,async`
+pub fn extract_filter(params: &HashMap<String, String>) -> QuestionFilter {
+  QuestionFilter {
+    search: params
+      .get("q")
+      .map(|q| q.trim().to_string())
+      .filter(|q| !q.is_empty()),
+    tags_any: params.get("tag").and_then(|tags| split_tags(tags)),
+    tags_all: params.get("tags_all").and_then(|tags| split_tags(tags)),
+  }
+}
+
+fn split_tags(tags: &str) -> Option<Vec<String>> {
+  let tags: Vec<String> = tags
+    .split(',')
+    .map(str::trim)
+    .filter(|tag| !tag.is_empty())
+    .map(String::from)
+    .collect();
+
+  if tags.is_empty() {
+    None
+  } else {
+    Some(tags)
+  }
+}
+
+/// ordering of the `/questions` route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuestionSort {
+  /// oldest first, or by relevance when searching
+  #[default]
+  Created,
+  /// highest score first, paged by offset
+  Score,
+}
+
+/// extract the sort param for the `/questions` route
+/// # Example query
+/// `/questions?sort=score` or `/questions?sort=created`
+pub fn extract_question_sort(params: &HashMap<String, String>) -> Result<QuestionSort, ApiError> {
+  match params.get("sort").map(String::as_str) {
+    None | Some("created") => Ok(QuestionSort::Created),
+    Some("score") => Ok(QuestionSort::Score),
+    Some(other) => Err(ApiError::InvalidParamError(format!("unknown sort '{}'", other))),
+  }
+}
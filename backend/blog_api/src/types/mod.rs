@@ -1,3 +1,10 @@
+pub mod account;
 pub mod answer;
+pub mod filter;
 pub mod pagination;
 pub mod question;
+pub mod review;
+pub mod revision;
+pub mod tag;
+pub mod trash;
+pub mod vote;
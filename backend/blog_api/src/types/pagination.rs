@@ -1,8 +1,10 @@
+use chrono::NaiveDateTime;
 use error_handler::ApiError;
+use serde::Serialize;
 use std::collections::HashMap;
 
 /// pagination structure, got from query params
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Pagination {
   /// the index of the first item in the range
   pub limit: Option<i32>,
@@ -10,12 +12,6 @@ pub struct Pagination {
   pub offset: i32,
 }
 
-impl Default for Pagination {
-  fn default() -> Self {
-    Pagination { limit: None, offset: 0 }
-  }
-}
-
 /// extract query params for the `/questions` route
 /// # Example query
 /// GET requests to this route can have a pagination attached so we can set a range like: 
@@ -39,3 +35,280 @@ pub fn extract_pagination(params: HashMap<String, String>) -> Result<Pagination,
 
   Err(ApiError::MissingParamError)
 }
+
+/// page size used when a cursor request doesn't set `limit`
+pub const DEFAULT_PAGE_SIZE: i32 = 20;
+/// upper bound for `limit` on cursor requests
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+/// extract offset query params for listings that hand out plain lists, like answers
+/// # Example query
+/// both params are optional, `limit` defaults to `DEFAULT_PAGE_SIZE` and is capped at `MAX_PAGE_SIZE`:
+/// `/questions/1/answers?limit=10&offset=20`
+pub fn extract_capped_pagination(params: &HashMap<String, String>) -> Result<Pagination, ApiError> {
+  let limit = match params.get("limit") {
+    Some(limit) => limit.parse().map_err(ApiError::ParseError)?,
+    None => DEFAULT_PAGE_SIZE,
+  };
+  let offset = match params.get("offset") {
+    Some(offset) => offset.parse().map_err(ApiError::ParseError)?,
+    None => 0,
+  };
+
+  if limit < 1 {
+    return Err(ApiError::InvalidParamError("limit must be positive".to_string()));
+  }
+  if offset < 0 {
+    return Err(ApiError::InvalidParamError("offset must not be negative".to_string()));
+  }
+
+  Ok(Pagination { limit: Some(limit.min(MAX_PAGE_SIZE)), offset })
+}
+
+/// position in the `(created_at, id)` ordering of a listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CursorKey {
+  pub created_at: NaiveDateTime,
+  pub id: i32,
+}
+
+/// opaque cursor handed out to clients as `next_cursor` and `prev_cursor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cursor {
+  /// items strictly after the key
+  After(CursorKey),
+  /// items strictly before the key
+  Before(CursorKey),
+  /// position in a listing ranked by something other than creation time,
+  /// like search relevance, where a keyset doesn't apply
+  Offset(i32),
+}
+
+impl Cursor {
+  pub fn encode(&self) -> String {
+    let raw = match self {
+      Cursor::After(key) => format!("a.{}.{}", key.created_at.timestamp_micros(), key.id),
+      Cursor::Before(key) => format!("b.{}.{}", key.created_at.timestamp_micros(), key.id),
+      Cursor::Offset(offset) => format!("o.{}", offset),
+    };
+
+    base64::encode_config(raw, base64::URL_SAFE_NO_PAD)
+  }
+
+  pub fn decode(cursor: &str) -> Result<Self, ApiError> {
+    let invalid = || ApiError::InvalidParamError("malformed cursor".to_string());
+
+    let raw = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+    let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+    let parts: Vec<&str> = raw.split('.').collect();
+
+    match parts.as_slice() {
+      ["o", offset] => match offset.parse() {
+        Ok(offset) if offset >= 0 => Ok(Cursor::Offset(offset)),
+        _ => Err(invalid()),
+      },
+      [direction, micros, id] => {
+        let micros: i64 = micros.parse().map_err(|_| invalid())?;
+        let created_at = NaiveDateTime::from_timestamp_opt(
+          micros.div_euclid(1_000_000),
+          (micros.rem_euclid(1_000_000) * 1_000) as u32,
+        ).ok_or_else(invalid)?;
+        let key = CursorKey { created_at, id: id.parse().map_err(|_| invalid())? };
+
+        match *direction {
+          "a" => Ok(Cursor::After(key)),
+          "b" => Ok(Cursor::Before(key)),
+          _ => Err(invalid()),
+        }
+      }
+      _ => Err(invalid()),
+    }
+  }
+}
+
+/// cursor pagination structure, got from query params
+#[derive(Debug)]
+pub struct CursorPagination {
+  /// the maximum number of items in the page
+  pub limit: i32,
+  /// where the page starts, `None` for the first page
+  pub cursor: Option<Cursor>,
+}
+
+/// extract cursor query params for listing routes
+/// # Example query
+/// both params are optional, `limit` defaults to `DEFAULT_PAGE_SIZE` and is capped at `MAX_PAGE_SIZE`:
+/// `/questions?limit=10&cursor=YS4xNjczMTIxMzgyMDAwMDAwLjQy`
+pub fn extract_cursor_pagination(params: &HashMap<String, String>) -> Result<CursorPagination, ApiError> {
+  let limit = match params.get("limit") {
+    Some(limit) => limit.parse().map_err(ApiError::ParseError)?,
+    None => DEFAULT_PAGE_SIZE,
+  };
+
+  if limit < 1 {
+    return Err(ApiError::InvalidParamError("limit must be positive".to_string()));
+  }
+
+  let cursor = match params.get("cursor") {
+    Some(cursor) => Some(Cursor::decode(cursor)?),
+    None => None,
+  };
+
+  Ok(CursorPagination { limit: limit.min(MAX_PAGE_SIZE), cursor })
+}
+
+/// a page of items returned by cursor paginated listings
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+  pub items: Vec<T>,
+  pub next_cursor: Option<String>,
+  pub prev_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+  /// builds a page out of up to `limit + 1` rows fetched in the direction of `cursor`,
+  /// the extra row only tells whether there is more data past the page
+  pub fn from_keyset(mut rows: Vec<(CursorKey, T)>, limit: i32, cursor: Option<Cursor>) -> Self {
+    let has_more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+
+    if let Some(Cursor::Before(_)) = cursor {
+      rows.reverse();
+    }
+
+    let first = rows.first().map(|(key, _)| Cursor::Before(*key));
+    let last = rows.last().map(|(key, _)| Cursor::After(*key));
+
+    // a cursor only ever points at rows we already handed out, so whatever
+    // lies on the side we came from is known to exist
+    let (prev_cursor, next_cursor) = match cursor {
+      None => (None, last.filter(|_| has_more)),
+      Some(Cursor::Before(_)) => (first.filter(|_| has_more), last),
+      Some(_) => (first, last.filter(|_| has_more)),
+    };
+
+    Page {
+      items: rows.into_iter().map(|(_, item)| item).collect(),
+      next_cursor: next_cursor.map(|cursor| cursor.encode()),
+      prev_cursor: prev_cursor.map(|cursor| cursor.encode()),
+    }
+  }
+
+  /// builds a page out of up to `limit + 1` rows fetched from `offset`
+  pub fn from_offset(mut items: Vec<T>, limit: i32, offset: i32) -> Self {
+    let has_more = items.len() > limit as usize;
+    items.truncate(limit as usize);
+
+    Page {
+      items,
+      next_cursor: has_more.then(|| Cursor::Offset(offset + limit).encode()),
+      prev_cursor: (offset > 0).then(|| Cursor::Offset((offset - limit).max(0)).encode()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key() -> CursorKey {
+    CursorKey {
+      created_at: NaiveDateTime::from_timestamp_opt(1_673_121_382, 123_456_000).unwrap(),
+      id: 42,
+    }
+  }
+
+  fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+  }
+
+  #[test]
+  fn cursors_survive_a_round_trip() {
+    for cursor in [Cursor::After(key()), Cursor::Before(key()), Cursor::Offset(40)] {
+      assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+  }
+
+  #[test]
+  fn cursors_keep_timestamps_before_1970() {
+    let key = CursorKey {
+      created_at: NaiveDateTime::from_timestamp_opt(-10, 500_000_000).unwrap(),
+      id: 1,
+    };
+    assert_eq!(Cursor::decode(&Cursor::After(key).encode()).unwrap(), Cursor::After(key));
+  }
+
+  #[test]
+  fn malformed_cursors_are_rejected() {
+    let encode = |raw: &str| base64::encode_config(raw, base64::URL_SAFE_NO_PAD);
+    let cursors = [
+      "not base64!".to_string(),
+      encode("garbage"),
+      encode("o.-1"),
+      encode("o.ten"),
+      encode("x.1673121382000000.42"),
+      encode("a.later.42"),
+      encode("a.1673121382000000"),
+      encode("a.1673121382000000.42.1"),
+      base64::encode_config([0xff, 0xfe], base64::URL_SAFE_NO_PAD),
+    ];
+
+    for cursor in cursors {
+      assert!(
+        matches!(Cursor::decode(&cursor), Err(ApiError::InvalidParamError(_))),
+        "{} was accepted",
+        cursor,
+      );
+    }
+  }
+
+  #[test]
+  fn cursor_pagination_defaults_and_caps_the_limit() {
+    let pagination = extract_cursor_pagination(&params(&[])).unwrap();
+    assert_eq!(pagination.limit, DEFAULT_PAGE_SIZE);
+    assert!(pagination.cursor.is_none());
+
+    let pagination = extract_cursor_pagination(&params(&[("limit", "1000")])).unwrap();
+    assert_eq!(pagination.limit, MAX_PAGE_SIZE);
+  }
+
+  #[test]
+  fn cursor_pagination_rejects_bad_params() {
+    assert!(matches!(extract_cursor_pagination(&params(&[("limit", "0")])), Err(ApiError::InvalidParamError(_))));
+    assert!(matches!(extract_cursor_pagination(&params(&[("limit", "ten")])), Err(ApiError::ParseError(_))));
+    assert!(matches!(
+      extract_cursor_pagination(&params(&[("cursor", "nope")])),
+      Err(ApiError::InvalidParamError(_))
+    ));
+  }
+
+  #[test]
+  fn capped_pagination_defaults_and_caps_the_limit() {
+    let pagination = extract_capped_pagination(&params(&[("sort", "score")])).unwrap();
+    assert_eq!((pagination.limit, pagination.offset), (Some(DEFAULT_PAGE_SIZE), 0));
+
+    let pagination = extract_capped_pagination(&params(&[("limit", "1000"), ("offset", "5")])).unwrap();
+    assert_eq!((pagination.limit, pagination.offset), (Some(MAX_PAGE_SIZE), 5));
+
+    assert!(matches!(extract_capped_pagination(&params(&[("limit", "0")])), Err(ApiError::InvalidParamError(_))));
+    assert!(matches!(extract_capped_pagination(&params(&[("offset", "-1")])), Err(ApiError::InvalidParamError(_))));
+  }
+
+  #[test]
+  fn keyset_pages_point_both_ways() {
+    let rows = |ids: &[i32]| ids.iter().map(|id| (CursorKey { id: *id, ..key() }, *id)).collect::<Vec<_>>();
+
+    let first = Page::from_keyset(rows(&[1, 2, 3]), 2, None);
+    assert_eq!(first.items, vec![1, 2]);
+    assert!(first.prev_cursor.is_none());
+    assert_eq!(
+      Cursor::decode(first.next_cursor.as_deref().unwrap()).unwrap(),
+      Cursor::After(CursorKey { id: 2, ..key() }),
+    );
+
+    let last = Page::from_keyset(rows(&[3]), 2, Some(Cursor::After(CursorKey { id: 2, ..key() })));
+    assert_eq!(last.items, vec![3]);
+    assert!(last.next_cursor.is_none());
+    assert!(last.prev_cursor.is_some());
+  }
+}
@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use crate::types::account::AccountId;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Question {
@@ -6,6 +8,16 @@ pub struct Question {
   pub title: String,
   pub content: String,
   pub tags: Option<Vec<String>>,
+  pub created_at: NaiveDateTime,
+  pub updated_at: NaiveDateTime,
+  /// author of the question, unset for questions asked before accounts existed
+  pub account_id: Option<AccountId>,
+  /// upvotes minus downvotes
+  #[serde(default)]
+  pub score: i32,
+  /// set while the question is in the trash
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Clone)]
@@ -18,3 +30,48 @@ pub struct NewQuestion {
   pub tags: Option<Vec<String>>,
 }
 
+/// partial update of a question, fields left out keep their current value
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuestionPatch {
+  /// optional, must match the id in the path when given
+  pub id: Option<QuestionId>,
+  pub title: Option<String>,
+  pub content: Option<String>,
+  /// left out keeps the tags, `null` clears them
+  #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+  pub tags: Option<Option<Vec<String>>>,
+}
+
+/// tells a field sent as `null` (`Some(None)`) apart from a missing one (`None`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de>,
+{
+  Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn patch(json: &str) -> QuestionPatch {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn missing_tags_are_kept() {
+    assert_eq!(patch(r#"{"title": "t"}"#).tags, None);
+  }
+
+  #[test]
+  fn null_tags_are_cleared() {
+    assert_eq!(patch(r#"{"tags": null}"#).tags, Some(None));
+  }
+
+  #[test]
+  fn given_tags_replace_the_old_ones() {
+    assert_eq!(patch(r#"{"tags": ["a", "b"]}"#).tags, Some(Some(vec!["a".to_string(), "b".to_string()])));
+    assert_eq!(patch(r#"{"tags": []}"#).tags, Some(Some(vec![])));
+  }
+}
@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+/// question or answer stored without moderation, waiting for an admin
+#[derive(Debug, Serialize, Clone)]
+pub struct Review {
+  pub id: i32,
+  pub question_id: Option<QuestionId>,
+  pub answer_id: Option<AnswerId>,
+  pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReviewItem {
+  Question(QuestionId),
+  Answer(AnswerId),
+}
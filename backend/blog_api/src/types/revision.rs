@@ -0,0 +1,188 @@
+use chrono::NaiveDateTime;
+use error_handler::ApiError;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashMap;
+
+use crate::types::account::AccountId;
+use crate::types::question::{NewQuestion, Question, QuestionId};
+
+/// title, content and tags of a question as they were saved once
+#[derive(Debug, Serialize, Clone)]
+pub struct Revision {
+  pub question_id: QuestionId,
+  /// counts up from 1 for every question
+  pub revision: i32,
+  pub title: String,
+  pub content: String,
+  pub tags: Option<Vec<String>>,
+  /// who saved this version, unset for questions asked before accounts existed
+  pub account_id: Option<AccountId>,
+  pub created_at: NaiveDateTime,
+}
+
+impl Revision {
+  /// the revision `question` is right after it was saved
+  pub fn of(question: &Question, revision: i32, account_id: Option<AccountId>) -> Self {
+    Revision {
+      question_id: question.id.clone(),
+      revision,
+      title: question.title.clone(),
+      content: question.content.clone(),
+      tags: question.tags.clone(),
+      account_id,
+      created_at: question.updated_at,
+    }
+  }
+}
+
+impl From<Revision> for NewQuestion {
+  fn from(revision: Revision) -> Self {
+    NewQuestion {
+      title: revision.title,
+      content: revision.content,
+      tags: revision.tags,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+  Equal,
+  Delete,
+  Insert,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DiffLine {
+  pub op: DiffOp,
+  /// the line without its line break
+  pub text: String,
+}
+
+/// line by line changes from one revision to another, tags count as one line each
+#[derive(Debug, Serialize, Clone)]
+pub struct RevisionDiff {
+  pub from: i32,
+  pub to: i32,
+  pub title: Vec<DiffLine>,
+  pub content: Vec<DiffLine>,
+  pub tags: Vec<DiffLine>,
+}
+
+impl RevisionDiff {
+  pub fn between(from: &Revision, to: &Revision) -> Self {
+    let tags = |revision: &Revision| revision.tags.as_deref().unwrap_or_default().join("\n");
+
+    RevisionDiff {
+      from: from.revision,
+      to: to.revision,
+      title: diff_lines(&from.title, &to.title),
+      content: diff_lines(&from.content, &to.content),
+      tags: diff_lines(&tags(from), &tags(to)),
+    }
+  }
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+  // a last line without a line break would otherwise differ from the same line followed by more
+  let terminated = |text: &str| if text.is_empty() || text.ends_with('\n') { text.to_string() } else { format!("{}\n", text) };
+  let (old, new) = (terminated(old), terminated(new));
+
+  TextDiff::from_lines(&old, &new)
+    .iter_all_changes()
+    .map(|change| DiffLine {
+      op: match change.tag() {
+        ChangeTag::Equal => DiffOp::Equal,
+        ChangeTag::Delete => DiffOp::Delete,
+        ChangeTag::Insert => DiffOp::Insert,
+      },
+      text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+    })
+    .collect()
+}
+
+/// revisions to compare, `None` when left out of the query
+#[derive(Debug, Default)]
+pub struct DiffRange {
+  pub from: Option<i32>,
+  pub to: Option<i32>,
+}
+
+/// extract `from` and `to` for the `/questions/{id}/revisions/diff` route.
+/// `to` defaults to the latest revision and `from` to the one before `to`
+pub fn extract_diff_range(params: &HashMap<String, String>) -> Result<DiffRange, ApiError> {
+  let revision = |name: &str| match params.get(name) {
+    Some(value) => value.parse().map(Some).map_err(ApiError::ParseError),
+    None => Ok(None),
+  };
+
+  Ok(DiffRange {
+    from: revision("from")?,
+    to: revision("to")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(diff: &[DiffLine]) -> Vec<(DiffOp, &str)> {
+    diff.iter().map(|line| (line.op, line.text.as_str())).collect()
+  }
+
+  #[test]
+  fn changed_lines_are_deleted_then_inserted() {
+    let diff = diff_lines("one\ntwo\nthree", "one\n2\nthree");
+    assert_eq!(lines(&diff), vec![
+      (DiffOp::Equal, "one"),
+      (DiffOp::Delete, "two"),
+      (DiffOp::Insert, "2"),
+      (DiffOp::Equal, "three"),
+    ]);
+  }
+
+  #[test]
+  fn a_missing_last_line_break_is_no_change() {
+    let diff = diff_lines("one\ntwo", "one\ntwo\n");
+    assert_eq!(lines(&diff), vec![(DiffOp::Equal, "one"), (DiffOp::Equal, "two")]);
+
+    let diff = diff_lines("one", "one\ntwo");
+    assert_eq!(lines(&diff), vec![(DiffOp::Equal, "one"), (DiffOp::Insert, "two")]);
+  }
+
+  #[test]
+  fn empty_text_has_no_lines() {
+    assert!(diff_lines("", "").is_empty());
+    assert_eq!(lines(&diff_lines("", "new")), vec![(DiffOp::Insert, "new")]);
+    assert_eq!(lines(&diff_lines("old", "")), vec![(DiffOp::Delete, "old")]);
+  }
+
+  #[test]
+  fn windows_line_breaks_are_dropped_from_the_text() {
+    assert_eq!(lines(&diff_lines("a\r\nb\r\n", "a\r\nc\r\n")), vec![
+      (DiffOp::Equal, "a"),
+      (DiffOp::Delete, "b"),
+      (DiffOp::Insert, "c"),
+    ]);
+  }
+
+  #[test]
+  fn tags_are_compared_one_per_line() {
+    let revision = |revision: i32, tags: Option<Vec<&str>>| Revision {
+      question_id: QuestionId(1),
+      revision,
+      title: "title".to_string(),
+      content: "content".to_string(),
+      tags: tags.map(|tags| tags.into_iter().map(String::from).collect()),
+      account_id: None,
+      created_at: NaiveDateTime::default(),
+    };
+
+    let diff = RevisionDiff::between(&revision(1, Some(vec!["rust", "warp"])), &revision(2, None));
+    assert_eq!((diff.from, diff.to), (1, 2));
+    assert_eq!(lines(&diff.title), vec![(DiffOp::Equal, "title")]);
+    assert_eq!(lines(&diff.tags), vec![(DiffOp::Delete, "rust"), (DiffOp::Delete, "warp")]);
+  }
+}
@@ -0,0 +1,29 @@
+use error_handler::ApiError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+  pub name: String,
+  /// number of questions carrying this tag
+  pub questions: i64,
+}
+
+/// ordering of the `/tags` route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagSort {
+  Name,
+  #[default]
+  Popularity,
+}
+
+/// extract the sort param for the `/tags` route
+/// # Example query
+/// `/tags?sort=name` or `/tags?sort=popularity`
+pub fn extract_tag_sort(params: &HashMap<String, String>) -> Result<TagSort, ApiError> {
+  match params.get("sort").map(String::as_str) {
+    None | Some("popularity") => Ok(TagSort::Popularity),
+    Some("name") => Ok(TagSort::Name),
+    Some(other) => Err(ApiError::InvalidParamError(format!("unknown sort '{}'", other))),
+  }
+}
@@ -0,0 +1,18 @@
+use serde::Serialize;
+
+use crate::types::answer::Answer;
+use crate::types::question::Question;
+
+/// soft deleted items, most recently deleted first
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Trash {
+  pub questions: Vec<Question>,
+  pub answers: Vec<Answer>,
+}
+
+/// items a purge removed for good
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Purged {
+  pub questions: u64,
+  pub answers: u64,
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+  Up,
+  Down,
+}
+
+impl Direction {
+  /// what the vote adds to the score
+  pub fn value(self) -> i32 {
+    match self {
+      Direction::Up => 1,
+      Direction::Down => -1,
+    }
+  }
+}
+
+/// body of `PUT /questions/{id}/vote` and `PUT /answers/{id}/vote`
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct NewVote {
+  pub direction: Direction,
+}
+
+#[derive(Debug, Clone)]
+pub enum VoteItem {
+  Question(QuestionId),
+  Answer(AnswerId),
+}
+
+/// score of an item right after a vote on it
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct Votes {
+  pub score: i32,
+  /// the voter's vote, unset once retracted
+  pub vote: Option<Direction>,
+}